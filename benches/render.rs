@@ -0,0 +1,61 @@
+#![allow(clippy::precedence)]
+
+use {
+    bevy::{asset::AssetPlugin, prelude::App},
+    bevy_fundsp::prelude::*,
+    criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion},
+};
+
+const SAMPLE_RATE: f32 = 44100.0;
+const FRAMES: usize = 44100;
+
+fn filtered_saw() -> impl AudioUnit32 {
+    saw_hz(220.0) >> lowpass_hz(1000.0, 1.0) >> split::<U2>() * 0.2
+}
+
+fn dsp_source<D: DspGraph + Copy>(dsp_graph: D) -> DspSource {
+    let mut app = App::new();
+
+    app.add_plugins((AssetPlugin::default(), DspPlugin::new(SAMPLE_RATE)))
+        .add_dsp_source(dsp_graph, SourceType::Dynamic);
+
+    app.world
+        .resource::<DspManager>()
+        .try_get_graph(dsp_graph)
+        .unwrap_or_else(|err| panic!("{err}"))
+}
+
+fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render one second");
+
+    group.bench_function("get_stereo", |b| {
+        let mut audio_unit: Box<dyn AudioUnit32> = Box::new(filtered_saw());
+        b.iter(|| {
+            for _ in 0..FRAMES {
+                black_box(audio_unit.get_stereo());
+            }
+        });
+    });
+
+    for block_size in [1, 16, 64] {
+        group.bench_with_input(
+            BenchmarkId::new("Iter", block_size),
+            &block_size,
+            |b, &block_size| {
+                let mut iter = dsp_source(filtered_saw)
+                    .with_block_size(block_size)
+                    .into_iter();
+                b.iter(|| {
+                    for _ in 0..FRAMES {
+                        black_box(iter.next());
+                    }
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
@@ -15,18 +15,6 @@ fn white_noise() -> impl AudioUnit32 {
     white() >> split::<U2>() * 0.2
 }
 
-fn play_noise(
-    mut commands: Commands,
-    mut assets: ResMut<Assets<DspSource>>,
-    dsp_manager: Res<DspManager>,
-) {
-    let source = assets.add(
-        dsp_manager
-            .get_graph(white_noise)
-            .unwrap_or_else(|| panic!("DSP source not found!")),
-    );
-    commands.spawn(AudioSourceBundle {
-        source,
-        ..default()
-    });
+fn play_noise(mut commands: Commands) {
+    commands.play_dsp_graph(white_noise, PlaybackSettings::ONCE);
 }
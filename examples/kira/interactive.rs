@@ -1,12 +1,16 @@
 #![allow(clippy::precedence)]
 
-use {bevy::prelude::*, bevy_fundsp::prelude::*, bevy_kira_audio::prelude::*};
+use {
+    bevy::prelude::*,
+    bevy_fundsp::{backend::kira::KiraBackend, prelude::*},
+    bevy_kira_audio::{Audio, AudioControl, AudioPlugin, AudioSource},
+};
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(AudioPlugin)
-        .add_plugins(DspPlugin::default())
+        .add_plugins(DspPlugin::default().with_backend::<KiraBackend>())
         .add_dsp_source(sine_wave, SourceType::Static { duration: 0.5 })
         .add_dsp_source(triangle_wave, SourceType::Static { duration: 0.5 })
         .add_systems(Update, interactive_audio)
@@ -33,7 +37,7 @@ fn interactive_audio(
         let source = dsp_manager
             .get_graph(sine_wave)
             .unwrap_or_else(|| panic!("DSP source not found!"));
-        let audio_source = DefaultBackend::convert_to_audio_source(source.clone());
+        let audio_source = KiraBackend::convert_to_audio_source(source.clone());
         let audio_source = assets.add(audio_source);
         audio.play(audio_source);
     }
@@ -42,7 +46,7 @@ fn interactive_audio(
         let source = dsp_manager
             .get_graph(triangle_wave)
             .unwrap_or_else(|| panic!("DSP source not found!"));
-        let audio_source = DefaultBackend::convert_to_audio_source(source.clone());
+        let audio_source = KiraBackend::convert_to_audio_source(source.clone());
         let audio_source = assets.add(audio_source);
         audio.play(audio_source);
     }
@@ -1,12 +1,16 @@
 #![allow(clippy::precedence)]
 
-use {bevy::prelude::*, bevy_fundsp::prelude::*, bevy_kira_audio::prelude::*};
+use {
+    bevy::prelude::*,
+    bevy_fundsp::{backend::kira::KiraBackend, prelude::*},
+    bevy_kira_audio::{Audio, AudioControl, AudioPlugin, AudioSource},
+};
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(AudioPlugin)
-        .add_plugins(DspPlugin::default())
+        .add_plugins(DspPlugin::default().with_backend::<KiraBackend>())
         .add_dsp_source(white_noise, SourceType::Static { duration: 60.0 })
         .add_systems(Startup, play_noise)
         .run();
@@ -21,10 +25,14 @@ fn play_noise(
     dsp_manager: Res<DspManager>,
     audio: Res<Audio>,
 ) {
-    let source = dsp_manager
-        .get_graph(white_noise)
-        .unwrap_or_else(|| panic!("DSP source not found!"));
-    let audio_source = DefaultBackend::convert_to_audio_source(source.clone());
-    let audio_source = assets.add(audio_source);
-    audio.play(audio_source);
+    let audio_source = dsp_manager
+        .try_get_graph(white_noise)
+        .and_then(KiraBackend::try_convert_to_audio_source);
+
+    match audio_source {
+        Ok(audio_source) => {
+            audio.play(assets.add(audio_source));
+        }
+        Err(err) => error!("{err}"),
+    }
 }
@@ -2,7 +2,7 @@
 
 use {
     bevy::prelude::*,
-    bevy_fundsp::prelude::*,
+    bevy_fundsp::{backend::oddio::OddioBackend, prelude::*},
     bevy_oddio::{Audio, AudioPlugin, AudioSource},
 };
 
@@ -10,7 +10,7 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(AudioPlugin::new())
-        .add_plugins(DspPlugin::default())
+        .add_plugins(DspPlugin::default().with_backend::<OddioBackend>())
         .add_dsp_source(sine_wave, SourceType::Static { duration: 0.5 })
         .add_dsp_source(triangle_wave, SourceType::Static { duration: 0.5 })
         .add_systems(Update, interactive_audio)
@@ -2,7 +2,7 @@
 
 use {
     bevy::prelude::*,
-    bevy_fundsp::prelude::*,
+    bevy_fundsp::{backend::oddio::OddioBackend, prelude::*},
     bevy_oddio::{Audio, AudioPlugin},
 };
 
@@ -10,7 +10,7 @@ fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .add_plugins(AudioPlugin::new())
-        .add_plugins(DspPlugin::default())
+        .add_plugins(DspPlugin::default().with_backend::<OddioBackend>())
         .add_dsp_source(white_noise, SourceType::Dynamic)
         .add_systems(PostStartup, play_noise)
         .run();
@@ -25,8 +25,10 @@ fn play_noise(
     dsp_manager: Res<DspManager>,
     mut audio: ResMut<Audio<[f32; 2], DspSource>>,
 ) {
-    let source = dsp_manager
-        .get_graph(white_noise)
-        .unwrap_or_else(|| panic!("DSP source not found!"));
-    audio.play_dsp(assets.as_mut(), &source);
+    match dsp_manager.try_get_graph(white_noise) {
+        Ok(source) => {
+            audio.play_dsp(assets.as_mut(), &source);
+        }
+        Err(err) => error!("{err}"),
+    }
 }
@@ -0,0 +1,244 @@
+//! Module for [`AudioClock`],
+//! which reports how far the audio output has progressed.
+
+use {
+    bevy::prelude::Resource,
+    std::sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// The position of the audio output, in frames.
+///
+/// Unlike [`Time`](bevy::time::Time),
+/// this advances with the audio output,
+/// so it can be used to sync gameplay to the audio,
+/// such as beats in a rhythm game.
+///
+/// With the backends that mix in their output,
+/// such as the [`CpalBackend`](crate::backend::cpal::CpalBackend)
+/// and the [`HeadlessBackend`](crate::backend::headless::HeadlessBackend),
+/// the clock counts the frames mixed by the output,
+/// and advances even when nothing is playing.
+///
+/// With the other backends, the clock counts the frames rendered
+/// by dynamic DSP sources as the backend pulls them,
+/// so it stops while no dynamic DSP source is playing.
+/// Static DSP sources are rendered ahead of time,
+/// so they do not advance the clock.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// const BEATS_PER_MINUTE: f64 = 120.0;
+///
+/// fn pulse_on_beat(audio_clock: Res<AudioClock>, mut query: Query<&mut Transform>) {
+///     let beat = (audio_clock.seconds() * BEATS_PER_MINUTE / 60.0).fract() as f32;
+///
+///     for mut transform in &mut query {
+///         transform.scale = Vec3::splat(1.5 - beat * 0.5);
+///     }
+/// }
+/// ```
+///
+/// For the position of a single DSP source, see [`SourceClock`].
+#[derive(Resource, Clone)]
+pub struct AudioClock {
+    frames: Arc<AtomicU64>,
+    sample_rate: f32,
+    /// Whether the clock is advanced by the output
+    /// instead of the DSP sources.
+    driven_by_output: Arc<AtomicBool>,
+}
+
+impl AudioClock {
+    pub(crate) fn new(sample_rate: f32) -> Self {
+        Self {
+            frames: Arc::default(),
+            sample_rate,
+            driven_by_output: Arc::default(),
+        }
+    }
+
+    /// Advance the clock from the output only,
+    /// ignoring the frames rendered by the DSP sources.
+    pub(crate) fn drive_by_output(&self) {
+        self.driven_by_output.store(true, Ordering::Relaxed);
+    }
+
+    /// Set the number of frames mixed by the output,
+    /// at the given sample rate of the output.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation
+    )]
+    pub(crate) fn set_output_frames(&self, frames: u64, sample_rate: f32) {
+        let ratio = f64::from(self.sample_rate) / f64::from(sample_rate);
+        let frames = (frames as f64 * ratio).round() as u64;
+
+        self.frames.store(frames, Ordering::Relaxed);
+    }
+
+    /// The number of frames played so far.
+    #[must_use]
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// The number of seconds played so far.
+    #[must_use]
+    pub fn seconds(&self) -> f64 {
+        to_seconds(self.frames(), self.sample_rate)
+    }
+
+    /// The sample rate the frames are counted in,
+    /// which is the sample rate of the [`DspManager`](crate::dsp_manager::DspManager).
+    #[must_use]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+}
+
+/// The position of a single [`DspSource`](crate::dsp_source::DspSource),
+/// in frames served to the backend.
+///
+/// Like [`Controls`](crate::controls::Controls),
+/// each DSP source from the [`DspManager`](crate::dsp_manager::DspManager)
+/// has its own clock, which is shared with its clones.
+/// Only dynamic DSP sources advance their clock.
+#[derive(Clone)]
+pub struct SourceClock {
+    frames: Arc<AtomicU64>,
+    sample_rate: f32,
+}
+
+impl SourceClock {
+    pub(crate) fn new(sample_rate: f32) -> Self {
+        Self {
+            frames: Arc::default(),
+            sample_rate,
+        }
+    }
+
+    /// The number of frames played so far.
+    #[must_use]
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// The number of seconds played so far.
+    #[must_use]
+    pub fn seconds(&self) -> f64 {
+        to_seconds(self.frames(), self.sample_rate)
+    }
+
+    /// The sample rate of the DSP source.
+    #[must_use]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+}
+
+/// Advances the clocks of a playing DSP source.
+pub(crate) struct ClockWriter {
+    source_clock: SourceClock,
+    audio_clock: Option<AudioClock>,
+    /// The frame of the audio clock when the DSP source started playing,
+    /// or `None` before its first frame is played.
+    start: Option<u64>,
+    /// The sample rate of the audio clock over the sample rate of the DSP source.
+    ratio: f64,
+}
+
+impl ClockWriter {
+    pub(crate) fn new(source_clock: SourceClock, audio_clock: Option<AudioClock>) -> Self {
+        let ratio = audio_clock.as_ref().map_or(1.0, |audio_clock| {
+            f64::from(audio_clock.sample_rate) / f64::from(source_clock.sample_rate)
+        });
+
+        Self {
+            source_clock,
+            audio_clock,
+            start: None,
+            ratio,
+        }
+    }
+
+    /// Set the number of frames the DSP source has played.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation
+    )]
+    pub(crate) fn set(&mut self, frames: u64) {
+        self.source_clock.frames.store(frames, Ordering::Relaxed);
+
+        let Some(audio_clock) = &self.audio_clock else {
+            return;
+        };
+
+        if audio_clock.driven_by_output.load(Ordering::Relaxed) {
+            return;
+        }
+
+        // The start is taken when the first frame is played,
+        // not when the DSP source is converted to an iterator.
+        let start = *self.start.get_or_insert_with(|| audio_clock.frames());
+
+        let frames = start + (frames as f64 * self.ratio).round() as u64;
+        // Several DSP sources play at once, so the furthest one wins.
+        audio_clock.frames.fetch_max(frames, Ordering::Relaxed);
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn to_seconds(frames: u64, sample_rate: f32) -> f64 {
+    frames as f64 / f64::from(sample_rate)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports)]
+
+    use {
+        crate::{dsp_manager::DspManager, dsp_source::SourceType},
+        fundsp::hacker32::*,
+    };
+
+    #[test]
+    fn clocks_advance_with_played_frames() {
+        let mut dsp_manager = DspManager::new(44100.0);
+        let audio_clock = dsp_manager.audio_clock().clone();
+
+        let sine = || sine_hz(440.0);
+
+        dsp_manager.add_graph(sine, SourceType::Dynamic).unwrap();
+
+        let first = dsp_manager.get_graph(sine).unwrap().with_block_size(64);
+        let first_clock = first.clock().clone();
+        let mut first = first.into_iter();
+
+        first.by_ref().take(100).for_each(drop);
+
+        assert_eq!(first_clock.frames(), 100);
+        assert_eq!(audio_clock.frames(), 100);
+
+        // The second source starts at the current position of the audio clock.
+        let second = dsp_manager.get_graph(sine).unwrap();
+        let second_clock = second.clock().clone();
+        let mut second = second.into_iter();
+
+        second.by_ref().take(50).for_each(drop);
+
+        assert_eq!(second_clock.frames(), 50);
+        assert_eq!(audio_clock.frames(), 150);
+
+        first.by_ref().take(44000).for_each(drop);
+
+        assert_eq!(first_clock.frames(), 44100);
+        assert_eq!(audio_clock.frames(), 44100);
+        assert!((audio_clock.seconds() - 1.0).abs() < f64::EPSILON);
+    }
+}
@@ -1,41 +1,86 @@
 //! Module for the [`Backend`] trait
 //! that is implemented for each audio plugin in the Bevy ecosystem.
+//!
+//! The backend features are additive.
+//! If several are enabled, the [`DefaultBackend`] is chosen
+//! in the order of `bevy_audio`, `kira`, `oddio` and `cpal_output`,
+//! or is the [`HeadlessBackend`](headless::HeadlessBackend) if none are enabled.
+//! Use [`DspPlugin::with_backend`](crate::DspPlugin::with_backend) to choose another one.
 
 use {
-    crate::dsp_source::DspSource,
-    bevy::{prelude::App, utils::default},
+    crate::{dsp_error::DspError, dsp_source::DspSource},
+    bevy::{asset::Asset, prelude::App, utils::default},
 };
 
 #[cfg(feature = "bevy_audio")]
 pub mod bevy_audio;
+#[cfg(feature = "cpal_output")]
+pub mod cpal;
+pub mod headless;
 #[cfg(feature = "kira")]
 pub mod kira;
 #[cfg(feature = "oddio")]
 pub mod oddio;
 
-/// The default backend.
+/// The default backend, playing DSP sources with `bevy_audio`.
 #[allow(clippy::module_name_repetitions)]
 #[cfg(feature = "bevy_audio")]
 pub type DefaultBackend = bevy_audio::BevyAudioBackend;
-/// The default backend.
+/// The default backend, playing DSP sources with `bevy_kira_audio`.
 #[allow(clippy::module_name_repetitions)]
-#[cfg(feature = "kira")]
+#[cfg(all(feature = "kira", not(feature = "bevy_audio")))]
 pub type DefaultBackend = kira::KiraBackend;
-/// The default backend.
+/// The default backend, playing DSP sources with `bevy_oddio`.
 #[allow(clippy::module_name_repetitions)]
-#[cfg(feature = "oddio")]
+#[cfg(all(feature = "oddio", not(any(feature = "bevy_audio", feature = "kira"))))]
 pub type DefaultBackend = oddio::OddioBackend;
+/// The default backend, playing DSP sources with its own `cpal` stream.
+#[allow(clippy::module_name_repetitions)]
+#[cfg(all(
+    feature = "cpal_output",
+    not(any(feature = "bevy_audio", feature = "kira", feature = "oddio"))
+))]
+pub type DefaultBackend = cpal::CpalBackend;
+/// The default backend when no backend feature is enabled,
+/// playing DSP sources into memory.
+#[allow(clippy::module_name_repetitions)]
+#[cfg(not(any(
+    feature = "bevy_audio",
+    feature = "kira",
+    feature = "oddio",
+    feature = "cpal_output"
+)))]
+pub type DefaultBackend = headless::HeadlessBackend;
 
 /// The backend trait used to convert [`DspSource`] into its concrete type.
 pub trait Backend: Send + Sync + 'static {
     /// The static audio source.
     /// Usually stores a collection of sound bytes.
-    type StaticAudioSource;
+    type StaticAudioSource: Asset;
 
     /// Initialization of App that is specific for the given Backend.
     fn init_app(app: &mut App);
     /// Convert the given [`DspSource`] to the defined static audio source.
-    fn convert_to_audio_source(dsp_source: DspSource) -> Self::StaticAudioSource;
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::DynamicSource`] if the DSP source is not static,
+    /// or another [`DspError`] if the rendered source cannot be written or decoded.
+    fn try_convert_to_audio_source(
+        dsp_source: DspSource,
+    ) -> Result<Self::StaticAudioSource, DspError>;
+
+    /// Convert the given [`DspSource`] to the defined static audio source.
+    ///
+    /// See [`Backend::try_convert_to_audio_source`] for the fallible version.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the DSP source cannot be converted.
+    #[must_use]
+    fn convert_to_audio_source(dsp_source: DspSource) -> Self::StaticAudioSource {
+        Self::try_convert_to_audio_source(dsp_source).unwrap_or_else(|err| panic!("{err}"))
+    }
 }
 
 /// Extension trait to add a helper method for playing DSP sources.
@@ -51,12 +96,35 @@ pub trait DspAudioExt {
     type Sink;
 
     /// Play the given [`DspSource`] with the given settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the DSP source cannot be played by this backend,
+    /// such as [`DspError::DynamicSource`] if it only plays static DSP sources,
+    /// or if the static DSP source cannot be rendered.
+    fn try_play_dsp_with_settings(
+        &mut self,
+        assets: &mut Self::Assets,
+        source: &DspSource,
+        settings: Self::Settings,
+    ) -> Result<Self::Sink, DspError>;
+
+    /// Play the given [`DspSource`] with the given settings.
+    ///
+    /// See [`DspAudioExt::try_play_dsp_with_settings`] for the fallible version.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the DSP source cannot be played.
     fn play_dsp_with_settings(
         &mut self,
         assets: &mut Self::Assets,
         source: &DspSource,
         settings: Self::Settings,
-    ) -> Self::Sink;
+    ) -> Self::Sink {
+        self.try_play_dsp_with_settings(assets, source, settings)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
 
     /// Play the given [`DspSource`] with the default settings.
     fn play_dsp(&mut self, assets: &mut Self::Assets, source: &DspSource) -> Self::Sink {
@@ -2,11 +2,24 @@
 
 use {
     super::Backend,
-    crate::dsp_source::{DspSource, IterMono},
+    crate::{
+        dsp_bus::DspBuses,
+        dsp_error::DspError,
+        dsp_graph::DspGraph,
+        dsp_manager::DspManager,
+        dsp_source::{Channels, DspSource, IterInterleaved, IterMono, SourceType},
+    },
     bevy::{
-        audio::AddAudioSource,
-        prelude::{App, AudioSource, Decodable},
+        audio::{AddAudioSource, AudioSinkPlayback, AudioSourceBundle, Volume},
+        ecs::system::EntityCommands,
+        log::error,
+        prelude::{
+            App, Assets, AudioBundle, AudioSink, AudioSource, Bundle, Commands, Component,
+            Decodable, Entity, GlobalVolume, PlaybackSettings, Query, Update, World,
+        },
     },
+    fundsp::hacker32::Shared,
+    std::time::Duration,
 };
 
 /// The backend for `bevy_audio`.
@@ -14,11 +27,92 @@ use {
 pub struct BevyAudioBackend;
 
 impl Decodable for DspSource {
-    type Decoder = IterMono;
+    type Decoder = DspDecoder;
     type DecoderItem = f32;
 
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
     fn decoder(&self) -> Self::Decoder {
-        self.clone().into_iter().into_mono()
+        let iter = self.clone().into_iter();
+
+        let (samples, channels) = match self.channels {
+            Channels::Mono => (DecoderSamples::Mono(iter.into_mono()), 1),
+            Channels::Stereo => (DecoderSamples::Stereo(iter.into_interleaved()), 2),
+        };
+
+        let (remaining, total_duration) = match self.source_type {
+            SourceType::Static { duration } => {
+                let frames = (self.sample_rate * duration).round() as usize;
+                (
+                    Some(frames * channels),
+                    Some(Duration::from_secs_f32(duration)),
+                )
+            }
+            SourceType::Dynamic => (None, None),
+        };
+
+        DspDecoder {
+            samples,
+            remaining,
+            total_duration,
+        }
+    }
+}
+
+/// The decoder used by `bevy_audio` to play a [`DspSource`].
+///
+/// Whether it is mono or stereo depends on [`DspSource::with_channels`].
+/// If the source is [static](SourceType::Static),
+/// the decoder stops after the given duration.
+pub struct DspDecoder {
+    samples: DecoderSamples,
+    remaining: Option<usize>,
+    total_duration: Option<Duration>,
+}
+
+enum DecoderSamples {
+    Mono(IterMono),
+    Stereo(IterInterleaved),
+}
+
+impl Iterator for DspDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.checked_sub(1)?;
+        }
+
+        match &mut self.samples {
+            DecoderSamples::Mono(iter) => iter.next(),
+            DecoderSamples::Stereo(iter) => iter.next(),
+        }
+    }
+}
+
+impl rodio::Source for DspDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.remaining
+    }
+
+    fn channels(&self) -> u16 {
+        match self.samples {
+            DecoderSamples::Mono(_) => 1,
+            DecoderSamples::Stereo(_) => 2,
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn sample_rate(&self) -> u32 {
+        let sample_rate = match &self.samples {
+            DecoderSamples::Mono(iter) => iter.0.sample_rate,
+            DecoderSamples::Stereo(iter) => iter.iter.sample_rate,
+        };
+
+        sample_rate as u32
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
     }
 }
 
@@ -36,7 +130,7 @@ impl rodio::Source for IterMono {
         self.0.sample_rate as u32
     }
 
-    fn total_duration(&self) -> Option<std::time::Duration> {
+    fn total_duration(&self) -> Option<Duration> {
         None
     }
 }
@@ -45,49 +139,348 @@ impl Backend for BevyAudioBackend {
     type StaticAudioSource = AudioSource;
 
     fn init_app(app: &mut App) {
-        app.add_audio_source::<DspSource>();
-    }
-
-    fn convert_to_audio_source(
-        dsp_source: crate::dsp_source::DspSource,
-    ) -> Self::StaticAudioSource {
-        let bytes = dsp_source.to_bytes().into();
-
-        AudioSource { bytes }
-    }
-}
-
-// fn play_queued_audio
-
-// impl DspAudioExt for Audio<AudioSource> {
-//     type Assets = Assets<AudioSource>;
-//     type Settings = PlaybackSettings;
-//     // type Sink = Handle<AudioSink>;
-
-//     fn play_dsp_with_settings(
-//         &mut self,
-//         assets: &mut Self::Assets,
-//         source: &DspSource,
-//         settings: Self::Settings,
-//     ) -> Self::Sink {
-//         let audio = BevyAudioBackend::convert_to_audio_source(source.clone());
-//         let handle = assets.add(audio);
-//         self.play_with_settings(handle, settings)
-//     }
-// }
-
-// impl DspAudioExt for DspSource {
-//     type Assets = Assets<DspSource>;
-//     type Settings = PlaybackSettings;
-//     type Sink = AudioSink;
-
-//     fn play_dsp_with_settings(
-//         &mut self,
-//         assets: &mut Self::Assets,
-//         source: &DspSource,
-//         settings: Self::Settings,
-//     ) -> Self::Sink {
-//         let handle = assets.add(source.clone());
-//         self.play_with_settings(handle, settings)
-//     }
-// }
+        app.add_audio_source::<DspSource>()
+            .add_systems(Update, update_bus_volumes);
+    }
+
+    fn try_convert_to_audio_source(
+        dsp_source: DspSource,
+    ) -> Result<Self::StaticAudioSource, DspError> {
+        let bytes = dsp_source.to_bytes()?;
+
+        Ok(AudioSource { bytes })
+    }
+}
+
+/// Extension trait to play DSP sources as entities in `bevy_audio`.
+///
+/// Static DSP sources are rendered into an [`AudioSource`],
+/// while dynamic DSP sources are played directly.
+/// Either way, the playing entity gets an
+/// [`AudioSink`](bevy::audio::AudioSink) once the audio starts.
+///
+/// If the DSP source cannot be played,
+/// the entity is kept and gets a [`DspPlayError`] instead,
+/// so other components can still be inserted into it.
+///
+/// The volume of the settings is multiplied by the gains of the bus of the DSP source
+/// and of every bus it feeds into, see [`DspBuses`].
+/// When these gains change, the volume of the [`AudioSink`] is set again,
+/// replacing any volume set on the sink directly.
+/// The effects of the buses are not applied.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// # fn sine_wave() -> impl AudioUnit32 { sine_hz(440.0) }
+/// fn play_sine(mut commands: Commands) {
+///     commands
+///         .play_dsp_graph(sine_wave, PlaybackSettings::DESPAWN)
+///         .insert(Name::new("Sine wave"));
+/// }
+/// ```
+pub trait DspCommandsExt<'w, 's> {
+    /// Spawn an entity playing the given [`DspSource`] with the given settings.
+    fn play_dsp_with_settings<'a>(
+        &'a mut self,
+        source: &DspSource,
+        settings: PlaybackSettings,
+    ) -> EntityCommands<'w, 's, 'a>;
+
+    /// Spawn an entity playing the given [`DspSource`] once.
+    fn play_dsp<'a>(&'a mut self, source: &DspSource) -> EntityCommands<'w, 's, 'a> {
+        self.play_dsp_with_settings(source, PlaybackSettings::ONCE)
+    }
+
+    /// Spawn an entity playing the given DSP graph with the given settings.
+    ///
+    /// The DSP source is taken from the [`DspManager`].
+    /// If the DSP graph is not registered,
+    /// this logs an error and inserts a [`DspPlayError`].
+    fn play_dsp_graph<'a, D: DspGraph>(
+        &'a mut self,
+        dsp_graph: D,
+        settings: PlaybackSettings,
+    ) -> EntityCommands<'w, 's, 'a>;
+}
+
+/// The error that prevented an entity spawned by [`DspCommandsExt`] from playing.
+///
+/// Query for it to clean up the entities that never play:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// fn despawn_failed(mut commands: Commands, failed: Query<Entity, Added<DspPlayError>>) {
+///     for entity in &failed {
+///         commands.entity(entity).despawn();
+///     }
+/// }
+/// ```
+#[derive(Component, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct DspPlayError(pub DspError);
+
+impl<'w, 's> DspCommandsExt<'w, 's> for Commands<'w, 's> {
+    fn play_dsp_with_settings<'a>(
+        &'a mut self,
+        source: &DspSource,
+        settings: PlaybackSettings,
+    ) -> EntityCommands<'w, 's, 'a> {
+        let entity = self.spawn_empty().id();
+        let source = source.clone();
+
+        self.add(move |world: &mut World| insert_audio_bundle(world, entity, source, settings));
+        self.entity(entity)
+    }
+
+    fn play_dsp_graph<'a, D: DspGraph>(
+        &'a mut self,
+        dsp_graph: D,
+        settings: PlaybackSettings,
+    ) -> EntityCommands<'w, 's, 'a> {
+        let entity = self.spawn_empty().id();
+
+        self.add(move |world: &mut World| {
+            match world.resource::<DspManager>().try_get_graph(dsp_graph) {
+                Ok(source) => insert_audio_bundle(world, entity, source, settings),
+                Err(err) => insert_error(world, entity, err),
+            }
+        });
+        self.entity(entity)
+    }
+}
+
+fn insert_audio_bundle(
+    world: &mut World,
+    entity: Entity,
+    source: DspSource,
+    mut settings: PlaybackSettings,
+) {
+    if let Some(bus_volume) = BusVolume::new(world, &source, &settings) {
+        settings.volume = Volume::new_absolute(bus_volume.volume * bus_volume.applied);
+        insert_bundle(world, entity, bus_volume);
+    }
+
+    match source.source_type {
+        SourceType::Static { .. } => match BevyAudioBackend::try_convert_to_audio_source(source) {
+            Ok(audio_source) => {
+                let source = world
+                    .resource_mut::<Assets<AudioSource>>()
+                    .add(audio_source);
+                insert_bundle(world, entity, AudioBundle { source, settings });
+            }
+            Err(err) => insert_error(world, entity, err),
+        },
+        SourceType::Dynamic => {
+            let source = world.resource_mut::<Assets<DspSource>>().add(source);
+            insert_bundle(world, entity, AudioSourceBundle { source, settings });
+        }
+    }
+}
+
+/// Multiplies the volume of an entity spawned by [`DspCommandsExt`]
+/// by the gains of the buses its DSP source is played through.
+#[derive(Component)]
+struct BusVolume {
+    /// The volume of the settings, including the global volume.
+    volume: f32,
+    gains: Vec<Shared<f32>>,
+    /// The gain the volume of the sink was last multiplied by.
+    applied: f32,
+}
+
+impl BusVolume {
+    /// Get the gains of the bus of the DSP source,
+    /// if the [`DspBuses`] were added by the [`DspPlugin`](crate::DspPlugin).
+    fn new(world: &World, source: &DspSource, settings: &PlaybackSettings) -> Option<Self> {
+        let gains = world.get_resource::<DspBuses>()?.gains(source.bus());
+
+        let volume = match settings.volume {
+            Volume::Relative(level) => {
+                let global_volume = world
+                    .get_resource::<GlobalVolume>()
+                    .map_or(1.0, |global_volume| global_volume.volume.get());
+
+                level.get() * global_volume
+            }
+            Volume::Absolute(level) => level.get(),
+        };
+
+        let mut bus_volume = Self {
+            volume,
+            gains,
+            applied: 1.0,
+        };
+        bus_volume.applied = bus_volume.gain();
+
+        Some(bus_volume)
+    }
+
+    fn gain(&self) -> f32 {
+        self.gains.iter().map(Shared::value).product()
+    }
+}
+
+/// Set the volume of the sinks again when the gains of their buses change.
+#[allow(clippy::float_cmp)]
+fn update_bus_volumes(mut sinks: Query<(&mut BusVolume, &AudioSink)>) {
+    for (mut bus_volume, sink) in &mut sinks {
+        let gain = bus_volume.gain();
+
+        if gain != bus_volume.applied {
+            sink.set_volume(bus_volume.volume * gain);
+            bus_volume.applied = gain;
+        }
+    }
+}
+
+/// Log the error and insert it into the entity.
+fn insert_error(world: &mut World, entity: Entity, err: DspError) {
+    error!("{err}");
+    insert_bundle(world, entity, DspPlayError(err));
+}
+
+/// Insert the bundle, unless the entity was despawned before the command was applied.
+fn insert_bundle(world: &mut World, entity: Entity, bundle: impl Bundle) {
+    if let Some(mut entity) = world.get_entity_mut(entity) {
+        entity.insert(bundle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports, clippy::float_cmp)]
+
+    use {
+        super::{BusVolume, DspCommandsExt, DspDecoder, DspPlayError},
+        crate::{
+            dsp_bus::{BusSettings, DspBus, DspBuses},
+            dsp_error::DspError,
+            dsp_manager::DspManager,
+            dsp_source::{Channels, DspSource, SourceType},
+            DEFAULT_SAMPLE_RATE,
+        },
+        bevy::{
+            asset::AssetPlugin,
+            audio::Volume,
+            ecs::system::CommandQueue,
+            prelude::{
+                AddAsset, App, AudioSource, Commands, Decodable, GlobalVolume, Handle, Name,
+                PlaybackSettings,
+            },
+        },
+        fundsp::hacker32::*,
+        rodio::Source,
+        std::time::Duration,
+    };
+
+    #[test]
+    fn static_source_is_finite() {
+        let source = DspSource::new(
+            || constant(440.0),
+            *DEFAULT_SAMPLE_RATE,
+            SourceType::Static { duration: 0.5 },
+        );
+
+        let decoder: DspDecoder = source.decoder();
+
+        assert_eq!(decoder.channels(), 2);
+        assert_eq!(decoder.total_duration(), Some(Duration::from_secs_f32(0.5)));
+        assert_eq!(decoder.count(), 22050 * 2);
+
+        let decoder = source.with_channels(Channels::Mono).decoder();
+
+        assert_eq!(decoder.channels(), 1);
+        assert_eq!(decoder.count(), 22050);
+    }
+
+    #[test]
+    fn dynamic_source_is_infinite() {
+        let source = DspSource::new(
+            || constant(440.0),
+            *DEFAULT_SAMPLE_RATE,
+            SourceType::Dynamic,
+        );
+
+        let decoder = source.decoder();
+
+        assert_eq!(decoder.total_duration(), None);
+        assert_eq!(decoder.take(100_000).count(), 100_000);
+    }
+
+    #[test]
+    fn commands_play_by_source_type() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .add_asset::<AudioSource>()
+            .add_asset::<DspSource>()
+            .insert_resource(DspManager::new(44100.0));
+
+        let noise = || white();
+        app.world
+            .resource_mut::<DspManager>()
+            .add_graph(noise, SourceType::Static { duration: 0.1 })
+            .unwrap();
+
+        let dynamic = DspSource::new(|| constant(0.0), 44100.0, SourceType::Dynamic);
+        let missing = || pink();
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+
+        let dynamic = commands.play_dsp(&dynamic).id();
+        let noise = commands.play_dsp_graph(noise, PlaybackSettings::LOOP).id();
+        let missing = commands
+            .play_dsp_graph(missing, PlaybackSettings::ONCE)
+            .insert(Name::new("Missing"))
+            .id();
+
+        queue.apply(&mut app.world);
+
+        assert!(app.world.get::<Handle<DspSource>>(dynamic).is_some());
+        assert!(app.world.get::<Handle<AudioSource>>(noise).is_some());
+        assert!(matches!(
+            app.world.get::<DspPlayError>(missing),
+            Some(DspPlayError(DspError::GraphNotFound(_)))
+        ));
+        assert!(app.world.get::<Name>(missing).is_some());
+    }
+
+    #[test]
+    fn commands_apply_the_gains_of_buses() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .add_asset::<DspSource>()
+            .insert_resource(GlobalVolume::new(0.5))
+            .insert_resource(
+                DspBuses::default().with_bus(DspBus::MUSIC, BusSettings::new().with_gain(0.5)),
+            );
+
+        let music =
+            DspSource::new(|| constant(0.0), 44100.0, SourceType::Dynamic).with_bus(DspBus::MUSIC);
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+
+        let music = commands
+            .play_dsp_with_settings(
+                &music,
+                PlaybackSettings::ONCE.with_volume(Volume::new_relative(0.5)),
+            )
+            .id();
+
+        queue.apply(&mut app.world);
+
+        let settings = app.world.get::<PlaybackSettings>(music).unwrap();
+        assert!(matches!(settings.volume, Volume::Absolute(level) if level.get() == 0.125));
+
+        let bus_volume = app.world.get::<BusVolume>(music).unwrap();
+        assert_eq!(bus_volume.volume, 0.25);
+
+        app.world
+            .resource::<DspBuses>()
+            .set_gain(&DspBus::MASTER, 0.5);
+        assert_eq!(bus_volume.gain(), 0.25);
+    }
+}
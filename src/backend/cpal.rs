@@ -0,0 +1,481 @@
+//! Implementation of a backend that plays DSP sources directly with `cpal`.
+
+use {
+    super::{Backend, DspAudioExt},
+    crate::{
+        audio_clock::AudioClock,
+        dsp_bus::DspBuses,
+        dsp_error::DspError,
+        dsp_manager::DspManager,
+        dsp_source::DspSource,
+        mixer::{Mixer, MixerAudioSource, MixerCommand, MixerSound, SoundId, MIX_BUFFER_SIZE},
+        DeviceError,
+    },
+    bevy::{
+        prelude::{AddAsset, App, Assets, Last, Res, Resource},
+        utils::HashSet,
+    },
+    cpal::{
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+        BufferSize, BuildStreamError, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    },
+    std::sync::{
+        mpsc::{Receiver, Sender},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+
+/// The backend that plays DSP sources with its own `cpal` output stream.
+///
+/// All playing DSP sources are mixed by a [`Mixer`] in the callback of the stream,
+/// without the buffering of another audio plugin.
+/// This gives the lowest latency, such as for playing instruments.
+///
+/// The stream is opened when the [`DspPlugin`](crate::DspPlugin) is built,
+/// using the [`CpalSettings`] resource if it was inserted before.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// use bevy_fundsp::backend::cpal::{CpalBackend, CpalOutput, CpalSettings};
+///
+/// # fn piano() -> impl AudioUnit32 { sine_hz(440.0) }
+/// fn play_note(dsp_manager: Res<DspManager>, output: Res<CpalOutput>) {
+///     if let Some(dsp_source) = dsp_manager.get_graph(piano) {
+///         output.play(&dsp_source).unwrap();
+///     }
+/// }
+///
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .insert_resource(CpalSettings::default().with_buffer_size(128))
+///     .add_plugins(DspPlugin::default().with_backend::<CpalBackend>())
+///     .add_dsp_source(piano, SourceType::Dynamic)
+///     .add_systems(Startup, play_note)
+///     .run();
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub struct CpalBackend;
+
+/// The settings of the output stream of the [`CpalBackend`].
+#[derive(Resource, Debug, Clone, Copy, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct CpalSettings {
+    buffer_size: Option<u32>,
+}
+
+impl CpalSettings {
+    /// Set the number of frames mixed in each callback of the stream.
+    ///
+    /// Smaller buffers have less latency,
+    /// but may crackle if the DSP sources cannot be mixed in time.
+    /// By default, the buffer size of the device is used.
+    #[must_use]
+    pub fn with_buffer_size(mut self, buffer_size: u32) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+
+    /// The number of frames mixed in each callback of the stream,
+    /// if it is not the default of the device.
+    #[must_use]
+    pub fn buffer_size(&self) -> Option<u32> {
+        self.buffer_size
+    }
+}
+
+/// The output of the [`CpalBackend`].
+///
+/// The [`Mixer`] is owned by the callback of the stream, so it is never locked.
+/// DSP sources are prepared on the thread that plays them,
+/// then sent to the callback through a channel,
+/// along with the commands to stop, pause, resume them and set their volume.
+/// Sounds removed from the mixer are sent back and dropped outside of the callback.
+///
+/// If no output device is available, the DSP sources are dropped instead of being played.
+#[derive(Resource, Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct CpalOutput {
+    queue: Sender<MixerCommand>,
+    playing: Arc<Mutex<PlayingSounds>>,
+    sample_rate: f32,
+}
+
+/// The sounds sent to the callback of the stream that were not removed yet.
+struct PlayingSounds {
+    ids: HashSet<SoundId>,
+    /// Receives the sounds removed from the mixer.
+    released: Receiver<MixerSound>,
+}
+
+/// Keeps the output stream of the [`CpalBackend`] playing.
+///
+/// The stream is not `Send` on every platform,
+/// so it is stored as a non-send resource.
+struct CpalStream(#[allow(dead_code)] Stream);
+
+impl CpalOutput {
+    /// Create the output and the mixer to be moved into the callback of the stream.
+    fn new(sample_rate: f32) -> (Self, Mixer) {
+        let mut mixer = Mixer::new(sample_rate);
+        let (queue, released) = mixer.queue();
+
+        let output = Self {
+            queue,
+            playing: Arc::new(Mutex::new(PlayingSounds {
+                ids: HashSet::default(),
+                released,
+            })),
+            sample_rate,
+        };
+
+        (output, mixer)
+    }
+
+    /// Play the given DSP source.
+    ///
+    /// The DSP source is rendered or generated before it is sent to the callback of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the static DSP source cannot be rendered.
+    pub fn play(&self, dsp_source: &DspSource) -> Result<SoundId, DspError> {
+        let sound = MixerSound::new(dsp_source, self.sample_rate)?;
+        Ok(self.send(sound))
+    }
+
+    /// Stop the given sound.
+    pub fn stop(&self, id: SoundId) {
+        self.lock().ids.remove(&id);
+        self.queue.send(MixerCommand::Stop(id)).ok();
+    }
+
+    /// Stop every sound.
+    pub fn stop_all(&self) {
+        self.lock().ids.clear();
+        self.queue.send(MixerCommand::StopAll).ok();
+    }
+
+    /// Pause the given sound.
+    pub fn pause(&self, id: SoundId) {
+        self.queue.send(MixerCommand::Pause(id)).ok();
+    }
+
+    /// Resume the given sound if it is paused.
+    pub fn resume(&self, id: SoundId) {
+        self.queue.send(MixerCommand::Resume(id)).ok();
+    }
+
+    /// Set the volume of the given sound as an amplitude,
+    /// where 1 is the original volume.
+    pub fn set_volume(&self, id: SoundId, volume: f32) {
+        self.queue.send(MixerCommand::SetVolume(id, volume)).ok();
+    }
+
+    /// Whether the given sound is still playing or paused.
+    ///
+    /// Sounds are reported as finished once the callback of the stream has removed them.
+    #[must_use]
+    pub fn is_playing(&self, id: SoundId) -> bool {
+        self.release();
+        self.lock().ids.contains(&id)
+    }
+
+    /// Send the prepared sound to the callback of the stream.
+    fn send(&self, sound: MixerSound) -> SoundId {
+        let id = sound.id();
+
+        // The receiver is dropped with the stream if it could not be opened,
+        // then the sound is dropped here.
+        if self.queue.send(MixerCommand::Play(sound)).is_ok() {
+            self.lock().ids.insert(id);
+        }
+
+        id
+    }
+
+    /// Drop the sounds removed from the mixer.
+    fn release(&self) {
+        let mut playing = self.lock();
+        let PlayingSounds { ids, released } = &mut *playing;
+
+        for sound in released.try_iter() {
+            ids.remove(&sound.id());
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PlayingSounds> {
+        self.playing.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Open the output stream of the default device, mixing the given mixer in its callback.
+fn open_stream(settings: CpalSettings, mixer: Mixer) -> Result<Stream, DeviceError> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or(DeviceError::NoOutputDevice)?;
+    let default_config = device
+        .default_output_config()
+        .map_err(DeviceError::DefaultStreamConfig)?;
+
+    let mut config = default_config.config();
+
+    if let Some(buffer_size) = settings.buffer_size {
+        config.buffer_size = BufferSize::Fixed(buffer_size);
+    }
+
+    let stream = match default_config.sample_format() {
+        SampleFormat::I8 => build_stream::<i8>(&device, &config, mixer),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, mixer),
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, mixer),
+        SampleFormat::I64 => build_stream::<i64>(&device, &config, mixer),
+        SampleFormat::U8 => build_stream::<u8>(&device, &config, mixer),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, mixer),
+        SampleFormat::U32 => build_stream::<u32>(&device, &config, mixer),
+        SampleFormat::U64 => build_stream::<u64>(&device, &config, mixer),
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, mixer),
+        SampleFormat::F64 => build_stream::<f64>(&device, &config, mixer),
+        _ => Err(BuildStreamError::StreamConfigNotSupported),
+    }
+    .map_err(DeviceError::BuildStream)?;
+
+    stream.play().map_err(DeviceError::PlayStream)?;
+
+    Ok(stream)
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut mixer: Mixer,
+) -> Result<Stream, BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels);
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| write_frames(&mut mixer, data, channels),
+        |err| bevy::log::error!("Error in the output stream: {err}"),
+        None,
+    )
+}
+
+/// Mix the next frames into the interleaved output of the stream.
+///
+/// Mono outputs get the average of both channels,
+/// and any channels after the first two are silent.
+fn write_frames<T>(mixer: &mut Mixer, data: &mut [T], channels: usize)
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let mut buffer = [[0.0; 2]; MIX_BUFFER_SIZE];
+
+    for chunk in data.chunks_mut(channels * MIX_BUFFER_SIZE) {
+        let frames = &mut buffer[..chunk.len() / channels];
+
+        mixer.mix(frames);
+
+        for (output, [left, right]) in chunk.chunks_mut(channels).zip(frames.iter()) {
+            match output {
+                [mono] => *mono = T::from_sample(f32::midpoint(*left, *right)),
+                [output_left, output_right, rest @ ..] => {
+                    *output_left = T::from_sample(*left);
+                    *output_right = T::from_sample(*right);
+                    rest.fill(T::EQUILIBRIUM);
+                }
+                [] => {}
+            }
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn release_sounds(output: Res<CpalOutput>) {
+    output.release();
+}
+
+impl Backend for CpalBackend {
+    type StaticAudioSource = MixerAudioSource;
+
+    #[allow(clippy::cast_precision_loss)]
+    fn init_app(app: &mut App) {
+        let settings = app
+            .world
+            .get_resource::<CpalSettings>()
+            .copied()
+            .unwrap_or_default();
+
+        let sample_rate = cpal::default_host()
+            .default_output_device()
+            .and_then(|device| device.default_output_config().ok())
+            .map_or_else(
+                || app.world.resource::<DspManager>().sample_rate(),
+                |config| config.sample_rate().0 as f32,
+            );
+
+        let (output, mut mixer) = CpalOutput::new(sample_rate);
+
+        mixer.set_buses(app.world.resource::<DspBuses>());
+        mixer.set_audio_clock(app.world.resource::<AudioClock>().clone());
+
+        match open_stream(settings, mixer) {
+            Ok(stream) => {
+                app.insert_non_send_resource(CpalStream(stream));
+            }
+            Err(err) => bevy::log::warn!("Cannot open the output stream. Error: {err}"),
+        }
+
+        app.add_asset::<MixerAudioSource>()
+            .insert_resource(settings)
+            .insert_resource(output)
+            .add_systems(Last, release_sounds);
+    }
+
+    fn try_convert_to_audio_source(
+        dsp_source: DspSource,
+    ) -> Result<Self::StaticAudioSource, DspError> {
+        MixerAudioSource::new(&dsp_source)
+    }
+}
+
+impl DspAudioExt for CpalOutput {
+    type Assets = Assets<MixerAudioSource>;
+    type Settings = ();
+    type Sink = SoundId;
+
+    /// Play the given [`DspSource`].
+    ///
+    /// Static DSP sources are added to the assets before being played.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the static DSP source cannot be rendered.
+    fn try_play_dsp_with_settings(
+        &mut self,
+        assets: &mut Self::Assets,
+        source: &DspSource,
+        _settings: Self::Settings,
+    ) -> Result<Self::Sink, DspError> {
+        match MixerAudioSource::new(source) {
+            Ok(audio_source) => {
+                let id = self.send(MixerSound::from_audio_source(
+                    &audio_source,
+                    self.sample_rate,
+                ));
+                assets.add(audio_source);
+                Ok(id)
+            }
+            Err(DspError::DynamicSource) => self.play(source),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports, clippy::float_cmp)]
+
+    use {
+        super::{write_frames, CpalOutput},
+        crate::dsp_source::{DspSource, SourceType},
+        fundsp::hacker32::*,
+    };
+
+    #[test]
+    fn stream_callback_writes_mixed_frames() {
+        let (output, mut mixer) = CpalOutput::new(100.0);
+        let dsp_source = DspSource::new(
+            || constant((0.5, 0.25)),
+            100.0,
+            SourceType::Static { duration: 0.5 },
+        );
+
+        let id = output.play(&dsp_source).unwrap();
+
+        // Dummy sinks standing in for the buffer of the stream.
+        let mut stereo = [0.0; 2 * 10];
+        write_frames(&mut mixer, &mut stereo, 2);
+
+        assert_eq!(stereo[..], [0.5, 0.25].repeat(10));
+
+        let mut surround = [1.0; 4 * 10];
+        write_frames(&mut mixer, &mut surround, 4);
+
+        assert_eq!(surround[..4], [0.5, 0.25, 0.0, 0.0]);
+        assert!(output.is_playing(id));
+
+        let mut mono = vec![1.0; 1000];
+        write_frames(&mut mixer, &mut mono, 1);
+
+        assert_eq!(mono[..30], [0.375; 30]);
+        assert_eq!(mono[30..], [0.0; 970]);
+        assert!(mixer.is_empty());
+        // The finished sound was sent back by the callback.
+        assert!(!output.is_playing(id));
+    }
+
+    #[test]
+    fn queued_sounds_are_mixed_in_every_sample_format() {
+        let (output, mut mixer) = CpalOutput::new(100.0);
+        let dsp_source = DspSource::new(|| constant(0.5), 100.0, SourceType::Dynamic);
+
+        let id = output.play(&dsp_source).unwrap();
+
+        let mut integers = [0_i16; 2 * 10];
+        write_frames(&mut mixer, &mut integers, 2);
+
+        assert_eq!(integers, [16384; 20]);
+
+        let mut doubles = [0.0_f64; 2 * 10];
+        write_frames(&mut mixer, &mut doubles, 2);
+
+        assert_eq!(doubles, [0.5; 20]);
+        assert!(output.is_playing(id));
+    }
+
+    #[test]
+    fn commands_are_applied_by_the_stream_callback() {
+        let (output, mut mixer) = CpalOutput::new(100.0);
+        let dsp_source = DspSource::new(|| constant(0.5), 100.0, SourceType::Dynamic);
+
+        let id = output.play(&dsp_source).unwrap();
+        let mut frames = [0.0; 2 * 10];
+
+        output.set_volume(id, 0.5);
+        write_frames(&mut mixer, &mut frames, 2);
+
+        assert_eq!(frames, [0.25; 20]);
+
+        output.pause(id);
+        write_frames(&mut mixer, &mut frames, 2);
+
+        assert_eq!(frames, [0.0; 20]);
+
+        output.resume(id);
+        write_frames(&mut mixer, &mut frames, 2);
+
+        assert_eq!(frames, [0.25; 20]);
+
+        output.stop(id);
+        assert!(!output.is_playing(id));
+
+        write_frames(&mut mixer, &mut frames, 2);
+
+        assert_eq!(frames, [0.0; 20]);
+        assert!(mixer.is_empty());
+    }
+
+    #[test]
+    fn sounds_are_dropped_without_an_output_stream() {
+        let (output, mixer) = CpalOutput::new(100.0);
+        let dsp_source = DspSource::new(|| constant(0.5), 100.0, SourceType::Dynamic);
+
+        drop(mixer);
+
+        let id = output.play(&dsp_source).unwrap();
+
+        assert!(!output.is_playing(id));
+    }
+}
@@ -0,0 +1,308 @@
+//! Implementation of a backend that plays DSP sources without an audio device.
+
+use {
+    super::{Backend, DspAudioExt},
+    crate::{
+        audio_clock::AudioClock,
+        dsp_bus::DspBuses,
+        dsp_error::DspError,
+        dsp_manager::DspManager,
+        dsp_source::DspSource,
+        mixer::{Mixer, MixerAudioSource, SoundId, MIX_BUFFER_SIZE},
+    },
+    bevy::{
+        prelude::{AddAsset, App, Assets, Res, ResMut, Resource, Update},
+        time::Time,
+    },
+    std::sync::{Mutex, PoisonError},
+    uuid::Uuid,
+};
+
+/// The backend that plays DSP sources into memory, without an audio device.
+///
+/// The DSP sources are mixed by the [`HeadlessOutput`] resource,
+/// which advances with [`Time`] on every [`Update`].
+/// This lets apps with `MinimalPlugins` run in CI or on dedicated servers,
+/// and lets tests inspect what was played.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// use bevy_fundsp::backend::headless::{HeadlessBackend, HeadlessOutput};
+///
+/// # fn beep() -> impl AudioUnit32 { sine_hz(440.0) }
+/// let mut app = App::new();
+///
+/// app.add_plugins((
+///     MinimalPlugins,
+///     AssetPlugin::default(),
+///     DspPlugin::new(44100.0).with_backend::<HeadlessBackend>(),
+/// ))
+/// .add_dsp_source(beep, SourceType::Static { duration: 0.5 });
+///
+/// let dsp_source = app.world.resource::<DspManager>().get_graph(beep).unwrap();
+/// let mut output = app.world.resource_mut::<HeadlessOutput>();
+///
+/// output.set_recording(true);
+/// output.play(&dsp_source).unwrap();
+/// output.advance(22050);
+///
+/// assert!(output.played().contains(&beep.id()));
+/// assert_eq!(output.recorded().len(), 22050);
+/// ```
+#[allow(clippy::module_name_repetitions)]
+pub struct HeadlessBackend;
+
+/// The output of the [`HeadlessBackend`].
+///
+/// By default, the mixed frames and the played DSP graphs are discarded.
+/// Use [`HeadlessOutput::set_recording`] to keep them.
+#[derive(Resource)]
+#[allow(clippy::module_name_repetitions)]
+pub struct HeadlessOutput {
+    // Dynamic DSP sources are not `Sync`.
+    mixer: Mutex<Mixer>,
+    recording: bool,
+    recorded: Vec<[f32; 2]>,
+    played: Vec<Uuid>,
+    frames: u64,
+    /// The fraction of a frame that was not mixed in the last update.
+    remainder: f64,
+}
+
+impl HeadlessOutput {
+    /// Create an output at the given sample rate.
+    #[must_use]
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            mixer: Mutex::new(Mixer::new(sample_rate)),
+            recording: false,
+            recorded: Vec::new(),
+            played: Vec::new(),
+            frames: 0,
+            remainder: 0.0,
+        }
+    }
+
+    /// The mixer of the playing DSP sources.
+    pub fn mixer(&mut self) -> &mut Mixer {
+        self.mixer.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Play the given DSP source.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the static DSP source cannot be rendered.
+    pub fn play(&mut self, dsp_source: &DspSource) -> Result<SoundId, DspError> {
+        let id = self.mixer().play(dsp_source)?;
+        self.record_played(dsp_source);
+        Ok(id)
+    }
+
+    fn record_played(&mut self, dsp_source: &DspSource) {
+        if self.recording {
+            self.played.push(dsp_source.dsp_graph.id());
+        }
+    }
+
+    /// Whether the mixed frames and the played DSP graphs are kept.
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    /// The frames mixed while recording.
+    #[must_use]
+    pub fn recorded(&self) -> &[[f32; 2]] {
+        &self.recorded
+    }
+
+    /// Take the frames mixed while recording, clearing them.
+    pub fn take_recorded(&mut self) -> Vec<[f32; 2]> {
+        std::mem::take(&mut self.recorded)
+    }
+
+    /// The IDs of the DSP graphs played while recording, in order.
+    #[must_use]
+    pub fn played(&self) -> &[Uuid] {
+        &self.played
+    }
+
+    /// Take the IDs of the DSP graphs played while recording, clearing them.
+    pub fn take_played(&mut self) -> Vec<Uuid> {
+        std::mem::take(&mut self.played)
+    }
+
+    /// The number of frames mixed so far.
+    #[must_use]
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Mix the given number of frames.
+    pub fn advance(&mut self, frames: usize) {
+        let mut buffer = [[0.0; 2]; MIX_BUFFER_SIZE];
+        let mut frames_left = frames;
+
+        while frames_left > 0 {
+            let size = frames_left.min(MIX_BUFFER_SIZE);
+
+            self.mixer().mix(&mut buffer[..size]);
+
+            if self.recording {
+                self.recorded.extend_from_slice(&buffer[..size]);
+            }
+
+            frames_left -= size;
+        }
+
+        self.frames += frames as u64;
+    }
+
+    /// Mix the frames that fit in the given number of seconds.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn advance_seconds(&mut self, seconds: f64) {
+        let frames = seconds * f64::from(self.mixer().sample_rate()) + self.remainder;
+
+        self.remainder = frames.fract();
+        self.advance(frames as usize);
+    }
+}
+
+impl Backend for HeadlessBackend {
+    type StaticAudioSource = MixerAudioSource;
+
+    fn init_app(app: &mut App) {
+        let sample_rate = app.world.resource::<DspManager>().sample_rate();
+        let mut output = HeadlessOutput::new(sample_rate);
+
+        output.mixer().set_buses(app.world.resource::<DspBuses>());
+        output
+            .mixer()
+            .set_audio_clock(app.world.resource::<AudioClock>().clone());
+
+        app.add_asset::<MixerAudioSource>()
+            .insert_resource(output)
+            .add_systems(Update, advance_headless_output);
+    }
+
+    fn try_convert_to_audio_source(
+        dsp_source: DspSource,
+    ) -> Result<Self::StaticAudioSource, DspError> {
+        MixerAudioSource::new(&dsp_source)
+    }
+}
+
+impl DspAudioExt for HeadlessOutput {
+    type Assets = Assets<MixerAudioSource>;
+    type Settings = ();
+    type Sink = SoundId;
+
+    /// Play the given [`DspSource`].
+    ///
+    /// Static DSP sources are added to the assets before being played.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the static DSP source cannot be rendered.
+    fn try_play_dsp_with_settings(
+        &mut self,
+        assets: &mut Self::Assets,
+        source: &DspSource,
+        _settings: Self::Settings,
+    ) -> Result<Self::Sink, DspError> {
+        let id = match MixerAudioSource::new(source) {
+            Ok(audio_source) => {
+                let id = self.mixer().play_audio_source(&audio_source);
+                assets.add(audio_source);
+                id
+            }
+            Err(DspError::DynamicSource) => self.mixer().play(source)?,
+            Err(err) => return Err(err),
+        };
+
+        self.record_played(source);
+        Ok(id)
+    }
+}
+
+/// Mix the frames of the [`HeadlessOutput`] that fit in the last update.
+fn advance_headless_output(time: Option<Res<Time>>, mut output: ResMut<HeadlessOutput>) {
+    let Some(time) = time else {
+        return;
+    };
+
+    output.advance_seconds(time.delta_seconds_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports)]
+
+    use {
+        super::{HeadlessBackend, HeadlessOutput},
+        crate::{
+            backend::DspAudioExt, dsp_graph::DspGraph, dsp_manager::DspManager,
+            dsp_source::SourceType, mixer::MixerAudioSource, DspAppExt, DspPlugin,
+        },
+        bevy::{
+            asset::AssetPlugin,
+            prelude::{App, Assets, Mut},
+        },
+        fundsp::hacker32::*,
+    };
+
+    fn beep() -> impl AudioUnit32 {
+        constant(0.3)
+    }
+
+    fn hum() -> impl AudioUnit32 {
+        constant(0.25)
+    }
+
+    #[test]
+    fn headless_app_records_played_sources() {
+        let mut app = App::new();
+        app.add_plugins((
+            AssetPlugin::default(),
+            DspPlugin::new(100.0).with_backend::<HeadlessBackend>(),
+        ))
+        .add_dsp_source(beep, SourceType::Static { duration: 0.5 })
+        .add_dsp_source(hum, SourceType::Dynamic);
+
+        let dsp_manager = app.world.resource::<DspManager>();
+        let beep_source = dsp_manager.get_graph(beep).unwrap();
+        let hum_source = dsp_manager.get_graph(hum).unwrap();
+
+        let beep_id =
+            app.world
+                .resource_scope(|world, mut assets: Mut<Assets<MixerAudioSource>>| {
+                    let mut output = world.resource_mut::<HeadlessOutput>();
+
+                    output.set_recording(true);
+                    output.play_dsp(&mut assets, &beep_source)
+                });
+
+        assert_eq!(app.world.resource::<Assets<MixerAudioSource>>().len(), 1);
+
+        let mut output = app.world.resource_mut::<HeadlessOutput>();
+
+        output.play(&hum_source).unwrap();
+        output.advance(100);
+
+        assert_eq!(output.take_played(), [beep.id(), hum.id()]);
+        assert_eq!(output.frames(), 100);
+        // Static DSP sources are rendered as 16-bit integers by default.
+        let beep_sample = 9830.0 / 32768.0;
+
+        assert_eq!(output.recorded()[..50], [[beep_sample + 0.25; 2]; 50]);
+        assert_eq!(output.recorded()[50..], [[0.25; 2]; 50]);
+        assert!(!output.mixer().is_playing(beep_id));
+        assert_eq!(output.mixer().len(), 1);
+
+        output.set_recording(false);
+        output.play(&hum_source).unwrap();
+
+        assert!(output.played().is_empty());
+    }
+}
@@ -1,45 +1,294 @@
 //! Implementation to integrate `bevy_fundsp` into `bevy_kira_audio`.
 
 use {
-    super::Backend,
-    crate::dsp_source::{DspSource, Iter, Source},
-    bevy::prelude::App,
-    bevy_kira_audio::AudioSource,
+    super::{Backend, DspAudioExt},
+    crate::{
+        controls::Controls,
+        dsp_bus::{DspBus, DspBuses},
+        dsp_error::DspError,
+        dsp_source::{DspSource, Iter, Source},
+    },
+    bevy::{
+        prelude::{App, Assets, Handle, Resource},
+        utils::HashMap,
+    },
+    bevy_kira_audio::{AudioChannel, AudioControl, AudioInstance, AudioSource},
+    fundsp::hacker32::{AudioUnit32, Shared},
     kira::{
         clock::clock_info::ClockInfoProvider,
+        manager::{backend::Backend as KiraManagerBackend, error::AddSubTrackError, AudioManager},
         modulator::value_provider::ModulatorValueProvider,
         sound::{
             static_sound::{StaticSoundData, StaticSoundSettings},
-            Sound, SoundData,
+            PlaybackState, Sound, SoundData,
+        },
+        track::{
+            effect::{Effect, EffectBuilder},
+            TrackBuilder, TrackHandle, TrackId, TrackRoutes,
         },
         OutputDestination,
     },
-    std::io::Cursor,
+    std::{
+        io::Cursor,
+        sync::{
+            atomic::{AtomicU32, AtomicU8, Ordering},
+            Arc,
+        },
+    },
 };
 
 impl SoundData for DspSource {
     type Error = ();
-    type Handle = ();
+    type Handle = DspSoundHandle;
+
+    /// Play the DSP source on the main track.
+    ///
+    /// See [`DspSoundData`] to play it on another track.
+    fn into_sound(self) -> Result<(Box<dyn kira::sound::Sound>, Self::Handle), Self::Error> {
+        DspSoundData::new(self).into_sound()
+    }
+}
+
+/// A dynamic [`DspSource`] played with a kira
+/// [`AudioManager`] on the given output destination.
+///
+/// ```no_run
+/// # use bevy_fundsp::prelude::*;
+/// # use bevy_fundsp::backend::kira::DspSoundData;
+/// # use kira::{manager::{backend::mock::MockBackend, AudioManager}, track::TrackBuilder};
+/// # fn play(manager: &mut AudioManager<MockBackend>, dsp_source: DspSource) {
+/// let track = manager.add_sub_track(TrackBuilder::new()).unwrap();
+/// let handle = manager
+///     .play(DspSoundData::new(dsp_source).with_output_destination(&track))
+///     .unwrap();
+/// # }
+/// ```
+///
+/// See [`KiraBuses`] to play DSP sources on the track of their bus.
+pub struct DspSoundData {
+    dsp_source: DspSource,
+    output_destination: OutputDestination,
+}
+
+impl DspSoundData {
+    /// Play the given DSP source on the main track.
+    #[must_use]
+    pub fn new(dsp_source: DspSource) -> Self {
+        Self {
+            dsp_source,
+            output_destination: OutputDestination::Track(TrackId::Main),
+        }
+    }
+
+    /// Play the DSP source on the given track or emitter.
+    #[must_use]
+    pub fn with_output_destination(
+        mut self,
+        output_destination: impl Into<OutputDestination>,
+    ) -> Self {
+        self.output_destination = output_destination.into();
+        self
+    }
+}
+
+impl SoundData for DspSoundData {
+    type Error = ();
+    type Handle = DspSoundHandle;
 
     fn into_sound(self) -> Result<(Box<dyn kira::sound::Sound>, Self::Handle), Self::Error> {
-        Ok((Box::new(self.into_iter()), ()))
+        let handle = DspSoundHandle {
+            shared: Arc::default(),
+            controls: self.dsp_source.controls.clone(),
+        };
+        let sound = DspSound {
+            iter: self.dsp_source.into_iter(),
+            shared: handle.shared.clone(),
+            output_destination: self.output_destination,
+        };
+
+        Ok((Box::new(sound), handle))
     }
 }
 
+/// Controls a dynamic [`DspSource`] played with a kira
+/// [`AudioManager`](kira::manager::AudioManager).
+///
+/// `bevy_kira_audio` can only play static sounds,
+/// so dynamic DSP sources are played with an audio manager instead,
+/// which returns this handle.
+///
+/// ```no_run
+/// # use bevy_fundsp::prelude::*;
+/// # use kira::manager::{backend::mock::MockBackend, AudioManager};
+/// # fn play(manager: &mut AudioManager<MockBackend>, dsp_source: DspSource) {
+/// let handle = manager.play(dsp_source).unwrap();
+///
+/// handle.set_volume(0.5);
+/// handle.set_panning(0.25);
+/// handle.controls().set("cutoff", 800.0);
+/// # }
+/// ```
+///
+/// Changes take effect on the next processed frame.
+/// Unlike the handles of kira, they are not tweened.
+pub struct DspSoundHandle {
+    shared: Arc<SoundShared>,
+    controls: Controls,
+}
+
+/// The state shared between a [`DspSoundHandle`] and its [`DspSound`].
+struct SoundShared {
+    volume: AtomicU32,
+    panning: AtomicU32,
+    state: AtomicU8,
+}
+
+impl Default for SoundShared {
+    fn default() -> Self {
+        Self {
+            volume: AtomicU32::new(1.0_f32.to_bits()),
+            panning: AtomicU32::new(0.5_f32.to_bits()),
+            state: AtomicU8::new(PLAYING),
+        }
+    }
+}
+
+const PLAYING: u8 = 0;
+const PAUSED: u8 = 1;
+const STOPPED: u8 = 2;
+
+impl SoundShared {
+    fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    fn panning(&self) -> f32 {
+        f32::from_bits(self.panning.load(Ordering::Relaxed))
+    }
+
+    fn state(&self) -> u8 {
+        self.state.load(Ordering::Relaxed)
+    }
+}
+
+impl DspSoundHandle {
+    /// The playback state of the DSP source.
+    ///
+    /// This is never [`PlaybackState::Pausing`] or [`PlaybackState::Stopping`],
+    /// as changes are not tweened.
+    #[must_use]
+    pub fn state(&self) -> PlaybackState {
+        match self.shared.state() {
+            PLAYING => PlaybackState::Playing,
+            PAUSED => PlaybackState::Paused,
+            _ => PlaybackState::Stopped,
+        }
+    }
+
+    /// Set the volume as an amplitude, where 1 is the original volume.
+    pub fn set_volume(&self, volume: f32) {
+        self.shared
+            .volume
+            .store(volume.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Set the panning, where 0 is hard left, 0.5 is center and 1 is hard right.
+    pub fn set_panning(&self, panning: f32) {
+        self.shared
+            .panning
+            .store(panning.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Pause the DSP source.
+    ///
+    /// The DSP graph is not processed while paused.
+    pub fn pause(&self) {
+        let _ = self.shared.state.compare_exchange(
+            PLAYING,
+            PAUSED,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Resume the DSP source if it is paused.
+    pub fn resume(&self) {
+        let _ = self.shared.state.compare_exchange(
+            PAUSED,
+            PLAYING,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Stop the DSP source.
+    ///
+    /// Stopped DSP sources cannot be resumed.
+    pub fn stop(&self) {
+        self.shared.state.store(STOPPED, Ordering::Relaxed);
+    }
+
+    /// The named parameters of the playing DSP source.
+    ///
+    /// See [`DspSource::controls`].
+    #[must_use]
+    pub fn controls(&self) -> &Controls {
+        &self.controls
+    }
+}
+
+/// The kira [`Sound`] of a [`DspSource`] that is controlled by a [`DspSoundHandle`].
+struct DspSound {
+    iter: Iter,
+    shared: Arc<SoundShared>,
+    output_destination: OutputDestination,
+}
+
+impl Sound for DspSound {
+    fn output_destination(&mut self) -> OutputDestination {
+        self.output_destination
+    }
+
+    fn process(
+        &mut self,
+        dt: f64,
+        clock_info_provider: &ClockInfoProvider,
+        modulator_value_provider: &ModulatorValueProvider,
+    ) -> kira::dsp::Frame {
+        if self.shared.state() != PLAYING {
+            return kira::dsp::Frame::ZERO;
+        }
+
+        let frame = self
+            .iter
+            .process(dt, clock_info_provider, modulator_value_provider);
+
+        (frame * self.shared.volume()).panned(self.shared.panning())
+    }
+
+    fn finished(&self) -> bool {
+        if self.iter.is_finished() {
+            self.shared.state.store(STOPPED, Ordering::Relaxed);
+        }
+
+        self.shared.state() == STOPPED
+    }
+}
+
+/// An [`Iter`] has no [`DspBus`], so it always plays on the main track.
+/// Play a [`DspSoundData`] or [`KiraBuses::sound_data`] to choose the track.
 impl Sound for Iter {
     fn output_destination(&mut self) -> OutputDestination {
-        OutputDestination::Track(kira::track::TrackId::Main)
+        OutputDestination::Track(TrackId::Main)
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn process(
         &mut self,
         dt: f64,
         _: &ClockInfoProvider,
         _: &ModulatorValueProvider,
     ) -> kira::dsp::Frame {
-        self.advance(dt as f32);
-        let frame = self.sample();
+        let frame = self.resample(dt);
         kira::dsp::Frame {
             left: frame[0],
             right: frame[1],
@@ -47,7 +296,139 @@ impl Sound for Iter {
     }
 
     fn finished(&self) -> bool {
-        false
+        self.is_finished()
+    }
+}
+
+/// The kira mixer tracks of the [`DspBuses`].
+///
+/// Each bus gets its own sub-track with the effect and gain of the bus,
+/// routed to the track of the bus it feeds into.
+/// The master bus is routed to the main track.
+///
+/// `bevy_kira_audio` does not expose its audio manager,
+/// so the buses are added to a kira [`AudioManager`] that plays dynamic DSP sources,
+/// see [`DspSoundHandle`].
+///
+/// ```no_run
+/// # use bevy_fundsp::prelude::*;
+/// # use bevy_fundsp::backend::kira::KiraBuses;
+/// # use kira::manager::{backend::mock::MockBackend, AudioManager};
+/// # fn play(manager: &mut AudioManager<MockBackend>, buses: &DspBuses, dsp_source: DspSource) {
+/// let kira_buses = KiraBuses::new(manager, buses).unwrap();
+/// let handle = manager
+///     .play(kira_buses.sound_data(dsp_source.with_bus(DspBus::MUSIC)))
+///     .unwrap();
+/// # }
+/// ```
+///
+/// The tracks are removed when this is dropped.
+pub struct KiraBuses {
+    tracks: HashMap<DspBus, TrackHandle>,
+}
+
+impl KiraBuses {
+    /// Add a sub-track for each of the given buses.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`AddSubTrackError`] if the sub-track limit of the manager is reached.
+    pub fn new<B: KiraManagerBackend>(
+        manager: &mut AudioManager<B>,
+        buses: &DspBuses,
+    ) -> Result<Self, AddSubTrackError> {
+        let routing = buses.routing();
+        let len = routing.len();
+        // Created from the master bus down, so each parent track exists before its inputs.
+        // kira processes the newest tracks first.
+        let mut tracks: Vec<TrackHandle> = Vec::with_capacity(len);
+
+        for (_, settings, output) in routing.iter().rev() {
+            let routes = output.map_or_else(TrackRoutes::new, |output| {
+                TrackRoutes::parent(&tracks[len - 1 - output])
+            });
+            let effect = BusEffect {
+                effect: settings.generate_effect(),
+                gain: settings.shared_gain(),
+            };
+
+            tracks.push(
+                manager.add_sub_track(TrackBuilder::new().routes(routes).with_effect(effect))?,
+            );
+        }
+
+        let tracks = routing
+            .into_iter()
+            .map(|(bus, ..)| bus)
+            .zip(tracks.into_iter().rev())
+            .collect();
+
+        Ok(Self { tracks })
+    }
+
+    /// The track of the given bus.
+    #[must_use]
+    pub fn track(&self, bus: &DspBus) -> Option<&TrackHandle> {
+        self.tracks.get(bus)
+    }
+
+    /// Play the given DSP source on the track of its bus,
+    /// or on the track of the master bus if its bus is missing.
+    #[must_use]
+    pub fn sound_data(&self, dsp_source: DspSource) -> DspSoundData {
+        let track = self
+            .track(dsp_source.bus())
+            .or_else(|| self.track(&DspBus::MASTER));
+
+        match track {
+            Some(track) => DspSoundData::new(dsp_source).with_output_destination(track),
+            None => DspSoundData::new(dsp_source),
+        }
+    }
+}
+
+/// Applies the effect and gain of a bus to its track.
+struct BusEffect {
+    effect: Option<Box<dyn AudioUnit32>>,
+    gain: Shared<f32>,
+}
+
+impl EffectBuilder for BusEffect {
+    type Handle = ();
+
+    fn build(self) -> (Box<dyn Effect>, Self::Handle) {
+        (Box::new(self), ())
+    }
+}
+
+impl Effect for BusEffect {
+    fn init(&mut self, sample_rate: u32) {
+        self.on_change_sample_rate(sample_rate);
+    }
+
+    fn on_change_sample_rate(&mut self, sample_rate: u32) {
+        if let Some(effect) = &mut self.effect {
+            effect.set_sample_rate(f64::from(sample_rate));
+        }
+    }
+
+    fn process(
+        &mut self,
+        input: kira::dsp::Frame,
+        _: f64,
+        _: &ClockInfoProvider,
+        _: &ModulatorValueProvider,
+    ) -> kira::dsp::Frame {
+        let mut output = [input.left, input.right];
+
+        if let Some(effect) = &mut self.effect {
+            effect.tick(&[input.left, input.right], &mut output);
+        }
+
+        kira::dsp::Frame {
+            left: output[0],
+            right: output[1],
+        } * self.gain.value()
     }
 }
 
@@ -60,12 +441,260 @@ impl Backend for KiraBackend {
 
     fn init_app(_app: &mut App) {}
 
-    fn convert_to_audio_source(dsp_source: DspSource) -> Self::StaticAudioSource {
-        let bytes = dsp_source.to_bytes();
+    fn try_convert_to_audio_source(
+        dsp_source: DspSource,
+    ) -> Result<Self::StaticAudioSource, DspError> {
+        let bytes = dsp_source.to_bytes()?;
         let cursored = Cursor::new(bytes);
-        AudioSource {
-            sound: StaticSoundData::from_cursor(cursored, StaticSoundSettings::new())
-                .unwrap_or_else(|err| panic!("Cannot read DSP source. Error: {err}")),
+        let sound = StaticSoundData::from_cursor(cursored, StaticSoundSettings::new())
+            .map_err(|err| DspError::Decode(Box::new(err)))?;
+
+        Ok(AudioSource { sound })
+    }
+}
+
+/// The settings used by [`DspAudioExt`] to play DSP sources
+/// on an [`AudioChannel`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KiraPlaybackSettings {
+    /// The volume as an amplitude, where 1 is the original volume.
+    pub volume: f64,
+    /// The panning, where 0 is hard left, 0.5 is center and 1 is hard right.
+    pub panning: f64,
+    /// Whether the sound loops.
+    pub looped: bool,
+}
+
+impl Default for KiraPlaybackSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            panning: 0.5,
+            looped: false,
         }
     }
 }
+
+/// A handle to a DSP source played on an [`AudioChannel`].
+///
+/// The [`AudioInstance`] controls the volume, panning and playback
+/// through [`Assets<AudioInstance>`],
+/// and the [`Controls`] set the parameters of the DSP graph.
+///
+/// `bevy_kira_audio` only plays static sounds,
+/// which are rendered with the values of the controls when played.
+/// Changing the controls afterwards affects the next play,
+/// but not the playing sound.
+#[allow(clippy::module_name_repetitions)]
+pub struct DspChannelHandle {
+    instance: Handle<AudioInstance>,
+    controls: Controls,
+}
+
+impl DspChannelHandle {
+    /// The handle to the playing [`AudioInstance`].
+    #[must_use]
+    pub fn instance(&self) -> &Handle<AudioInstance> {
+        &self.instance
+    }
+
+    /// The named parameters of the DSP source.
+    #[must_use]
+    pub fn controls(&self) -> &Controls {
+        &self.controls
+    }
+}
+
+impl<T: Resource> DspAudioExt for AudioChannel<T> {
+    type Assets = Assets<AudioSource>;
+    type Settings = KiraPlaybackSettings;
+    type Sink = DspChannelHandle;
+
+    /// Play the given static [`DspSource`] on this channel.
+    ///
+    /// `bevy_kira_audio` can only play static sounds.
+    /// Play dynamic DSP sources with a kira
+    /// [`AudioManager`](kira::manager::AudioManager) instead, see [`DspSoundHandle`].
+    ///
+    /// The [`DspBus`] of the DSP source is ignored,
+    /// as `bevy_kira_audio` plays it on this channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::DynamicSource`] if the DSP source is dynamic,
+    /// or another [`DspError`] if it cannot be rendered.
+    fn try_play_dsp_with_settings(
+        &mut self,
+        assets: &mut Self::Assets,
+        source: &DspSource,
+        settings: Self::Settings,
+    ) -> Result<Self::Sink, DspError> {
+        let audio_source = KiraBackend::try_convert_to_audio_source(source.clone())?;
+        let mut command = self.play(assets.add(audio_source));
+
+        command
+            .with_volume(settings.volume)
+            .with_panning(settings.panning);
+
+        if settings.looped {
+            command.looped();
+        }
+
+        Ok(DspChannelHandle {
+            instance: command.handle(),
+            controls: source.controls().clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports)]
+
+    use {
+        super::{KiraBuses, KiraPlaybackSettings},
+        crate::{
+            backend::DspAudioExt,
+            controls::Controls,
+            dsp_bus::{BusSettings, DspBus, DspBuses},
+            dsp_error::DspError,
+            dsp_graph::controllable,
+            dsp_source::{DspSource, SourceType},
+        },
+        bevy::{
+            asset::AssetPlugin,
+            prelude::{AddAsset, App, Assets},
+        },
+        bevy_kira_audio::{AudioChannel, AudioSource, MainTrack},
+        fundsp::hacker32::*,
+        kira::{
+            dsp::Frame,
+            manager::{
+                backend::mock::{MockBackend, MockBackendSettings},
+                AudioManager, AudioManagerSettings,
+            },
+            sound::PlaybackState,
+        },
+    };
+
+    #[test]
+    fn sound_handle_controls_playback() {
+        let mut manager = AudioManager::<MockBackend>::new(AudioManagerSettings {
+            backend_settings: MockBackendSettings { sample_rate: 44100 },
+            ..Default::default()
+        })
+        .unwrap();
+
+        let level = controllable(|controls: &Controls| var(&controls.shared("level", 0.5)));
+        let dsp_source = DspSource::new(level, 44100.0, SourceType::Dynamic).with_block_size(1);
+
+        let handle = manager.play(dsp_source).unwrap();
+
+        let mut process = || {
+            let backend = manager.backend_mut();
+            backend.on_start_processing();
+            (0..16).map(|_| backend.process()).last().unwrap()
+        };
+
+        let frame = process();
+        assert!((frame.left - 0.5).abs() < 1e-4 && (frame.right - 0.5).abs() < 1e-4);
+
+        handle.controls().set("level", 1.0);
+        handle.set_volume(0.5);
+        handle.set_panning(0.0);
+
+        let frame = process();
+        assert!((frame.left - 0.5 * std::f32::consts::SQRT_2).abs() < 1e-4);
+        assert!(frame.right.abs() < 1e-4);
+
+        handle.pause();
+        assert_eq!(handle.state(), PlaybackState::Paused);
+        assert_eq!(process(), Frame::ZERO);
+
+        handle.resume();
+        assert_eq!(handle.state(), PlaybackState::Playing);
+        assert!(process().left > 0.0);
+
+        handle.stop();
+        assert_eq!(handle.state(), PlaybackState::Stopped);
+        assert_eq!(process(), Frame::ZERO);
+    }
+
+    #[test]
+    fn buses_route_sounds_to_their_tracks() {
+        let mut manager = AudioManager::<MockBackend>::new(AudioManagerSettings {
+            backend_settings: MockBackendSettings { sample_rate: 44100 },
+            ..Default::default()
+        })
+        .unwrap();
+
+        let buses = DspBuses::default()
+            .with_bus(DspBus::MUSIC, BusSettings::new().with_gain(0.5))
+            .with_bus(
+                DspBus::new("echo"),
+                BusSettings::new()
+                    .with_effect(|| (pass() * 2.0) | (pass() * 2.0))
+                    .with_output(DspBus::MUSIC),
+            );
+        let kira_buses = KiraBuses::new(&mut manager, &buses).unwrap();
+
+        let dsp_source = DspSource::new(|| constant(0.25), 44100.0, SourceType::Dynamic)
+            .with_bus(DspBus::new("echo"));
+
+        manager.play(kira_buses.sound_data(dsp_source)).unwrap();
+
+        let mut process = || {
+            let backend = manager.backend_mut();
+            backend.on_start_processing();
+            (0..16).map(|_| backend.process()).last().unwrap()
+        };
+
+        let frame = process();
+        assert!((frame.left - 0.25).abs() < 1e-4 && (frame.right - 0.25).abs() < 1e-4);
+
+        buses.set_gain(&DspBus::MUSIC, 0.0);
+        assert!(process().left.abs() < 1e-4);
+    }
+
+    #[test]
+    fn channels_return_an_error_for_dynamic_sources() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .add_asset::<AudioSource>();
+
+        let mut channel = AudioChannel::<MainTrack>::default();
+        let mut assets = app.world.resource_mut::<Assets<AudioSource>>();
+        let dsp_source = DspSource::new(|| constant(0.5), 44100.0, SourceType::Dynamic);
+
+        assert!(matches!(
+            channel.try_play_dsp_with_settings(
+                &mut assets,
+                &dsp_source,
+                KiraPlaybackSettings::default()
+            ),
+            Err(DspError::DynamicSource)
+        ));
+    }
+
+    #[test]
+    fn channel_handles_share_the_controls_of_the_source() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .add_asset::<AudioSource>();
+
+        let mut channel = AudioChannel::<MainTrack>::default();
+        let mut assets = app.world.resource_mut::<Assets<AudioSource>>();
+        let dsp_source = DspSource::new(
+            || constant(0.5),
+            44100.0,
+            SourceType::Static { duration: 0.1 },
+        );
+
+        let handle = channel
+            .try_play_dsp_with_settings(&mut assets, &dsp_source, KiraPlaybackSettings::default())
+            .unwrap();
+        handle.controls().set("gain", 0.5);
+
+        assert_eq!(dsp_source.controls().get("gain"), Some(0.5));
+    }
+}
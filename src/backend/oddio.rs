@@ -2,17 +2,33 @@
 
 use {
     super::{Backend, DspAudioExt},
-    crate::dsp_source::{DspSource, Iter, IterMono, Source, SourceType},
-    bevy::prelude::{App, Assets, Handle},
+    crate::{
+        dsp_bus::DspBus,
+        dsp_error::DspError,
+        dsp_source::{DspSource, Iter, IterMono, Source},
+    },
+    bevy::{
+        log::warn,
+        prelude::{App, Assets, Handle},
+    },
     bevy_oddio::{
         oddio::{Frames, Sample, Signal},
         output::AudioSink,
         Audio, AudioApp, AudioSource, ToSignal,
     },
-    std::{cell::RefCell, rc::Rc},
+    std::cell::RefCell,
 };
 
 /// The backend for `bevy_oddio`.
+///
+/// The renders of static DSP sources are cached as shared frames,
+/// but `bevy_oddio` stores its frames in an `oddio::Frames`,
+/// so converting a static DSP source still copies its frames every time.
+/// Convert it once and keep the handle to the audio source
+/// to play it often.
+///
+/// `bevy_oddio` has no submixes, so DSP sources cannot be played through a bus
+/// other than the [`DspBus::MASTER`] bus.
 #[allow(clippy::module_name_repetitions)]
 pub struct OddioBackend;
 
@@ -24,14 +40,57 @@ impl Backend for OddioBackend {
     }
 
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-    fn convert_to_audio_source(dsp_source: DspSource) -> Self::StaticAudioSource {
+    fn try_convert_to_audio_source(
+        dsp_source: DspSource,
+    ) -> Result<Self::StaticAudioSource, DspError> {
+        check_bus(&dsp_source)?;
+
+        let sample_rate = dsp_source.sample_rate;
+
+        let frames = dsp_source.into_exact_size_iter()?;
+
+        let frames = Frames::from_iter(sample_rate as u32, frames);
+
+        Ok(AudioSource { frames })
+    }
+}
+
+impl OddioBackend {
+    /// Convert the given static [`DspSource`] into an audio source of mono frames,
+    /// averaging both channels.
+    ///
+    /// This stores half the samples of [`Backend::try_convert_to_audio_source`],
+    /// and should be used for DSP sources with
+    /// [`Channels::Mono`](crate::dsp_source::Channels::Mono).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the DSP source is dynamic, cannot be rendered,
+    /// or is played through a bus.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn try_convert_to_mono_audio_source(
+        dsp_source: DspSource,
+    ) -> Result<AudioSource<Sample>, DspError> {
+        check_bus(&dsp_source)?;
+
         let sample_rate = dsp_source.sample_rate;
 
-        let frames = dsp_source.into_exact_size_iter();
+        let frames = dsp_source
+            .into_exact_size_iter()?
+            .map(|[left, right]| f32::midpoint(left, right));
 
         let frames = Frames::from_iter(sample_rate as u32, frames);
 
-        AudioSource { frames }
+        Ok(AudioSource { frames })
+    }
+}
+
+/// Return [`DspError::UnsupportedBus`] if the DSP source is played through a bus.
+fn check_bus(dsp_source: &DspSource) -> Result<(), DspError> {
+    if *dsp_source.bus() == DspBus::MASTER {
+        Ok(())
+    } else {
+        Err(DspError::UnsupportedBus(dsp_source.bus().clone()))
     }
 }
 
@@ -39,33 +98,28 @@ impl ToSignal for DspSource {
     type Settings = ();
     type Signal = Iter;
 
+    /// Generate the signal of the DSP source.
+    ///
+    /// This cannot fail, so DSP sources played through a bus
+    /// log a warning and are played straight to the output.
     fn to_signal(&self, _settings: Self::Settings) -> Self::Signal {
+        if let Err(err) = check_bus(self) {
+            warn!("{err}");
+        }
+
         self.clone().into_iter()
     }
 }
 
 impl DspSource {
-    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
     pub(crate) fn into_exact_size_iter(
         self,
-    ) -> ExactSizeIter<impl Iterator<Item = [f32; 2]> + ExactSizeIterator> {
-        let audio_unit = Rc::new(RefCell::new(self.dsp_graph.generate_graph()));
-        let duration = match self.source_type {
-            SourceType::Static { duration } => duration,
-            SourceType::Dynamic => {
-                panic!("Cannot convert dynamic DSP source into exact size iterator")
-            }
-        };
-
-        let number_of_frames = (self.sample_rate * duration).round() as usize;
-
-        let collection =
-            (0..number_of_frames).map(|_| audio_unit.clone().borrow_mut().get_stereo().into());
-
-        ExactSizeIter {
-            sample_rate: self.sample_rate,
-            collection: RefCell::new(collection.collect::<Vec<_>>().into_iter()),
-        }
+    ) -> Result<ExactSizeIter<impl ExactSizeIterator<Item = [f32; 2]>>, DspError> {
+        let frames = self.to_frames()?;
+
+        Ok(ExactSizeIter {
+            collection: RefCell::new((0..frames.len()).map(move |index| frames[index])),
+        })
     }
 }
 
@@ -73,25 +127,9 @@ pub(crate) struct ExactSizeIter<I>
 where
     I: Iterator<Item = [f32; 2]> + ExactSizeIterator,
 {
-    sample_rate: f32,
     collection: RefCell<I>,
 }
 
-impl<I> Source for ExactSizeIter<I>
-where
-    I: Iterator<Item = [f32; 2]> + ExactSizeIterator,
-{
-    type Frame = [f32; 2];
-
-    fn sample_rate(&self) -> f32 {
-        self.sample_rate
-    }
-
-    fn sample(&self) -> Self::Frame {
-        self.collection.borrow_mut().next().unwrap_or_default()
-    }
-}
-
 impl<I> Iterator for ExactSizeIter<I>
 where
     I: Iterator<Item = [f32; 2]> + ExactSizeIterator,
@@ -116,11 +154,14 @@ impl Signal for Iter {
     type Frame = [f32; 2];
 
     fn sample(&self, interval: f32, out: &mut [Self::Frame]) {
-        self.advance(interval);
         for out_frame in out {
-            *out_frame = Source::sample(self);
+            *out_frame = self.resample(f64::from(interval));
         }
     }
+
+    fn is_finished(&self) -> bool {
+        Iter::is_finished(self)
+    }
 }
 
 impl Signal for IterMono {
@@ -128,12 +169,14 @@ impl Signal for IterMono {
     type Frame = Sample;
 
     fn sample(&self, interval: f32, out: &mut [Self::Frame]) {
-        self.advance(interval);
         for out_frame in out {
-            let frame = Source::sample(self);
-            *out_frame = frame;
+            *out_frame = self.resample(f64::from(interval));
         }
     }
+
+    fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
 }
 
 impl DspAudioExt for Audio<[f32; 2], AudioSource<[f32; 2]>> {
@@ -141,15 +184,38 @@ impl DspAudioExt for Audio<[f32; 2], AudioSource<[f32; 2]>> {
     type Settings = <AudioSource<[f32; 2]> as ToSignal>::Settings;
     type Sink = Handle<AudioSink<AudioSource<[f32; 2]>>>;
 
-    fn play_dsp_with_settings(
+    fn try_play_dsp_with_settings(
+        &mut self,
+        assets: &mut Self::Assets,
+        source: &DspSource,
+        settings: Self::Settings,
+    ) -> Result<Self::Sink, DspError> {
+        let audio_source = OddioBackend::try_convert_to_audio_source(source.clone())?;
+        let source_handle = assets.add(audio_source);
+        Ok(self.play(source_handle, settings))
+    }
+}
+
+impl DspAudioExt for Audio<Sample, AudioSource<Sample>> {
+    type Assets = Assets<AudioSource<Sample>>;
+    type Settings = <AudioSource<Sample> as ToSignal>::Settings;
+    type Sink = Handle<AudioSink<AudioSource<Sample>>>;
+
+    /// Play the given static [`DspSource`] as mono frames.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the DSP source is dynamic, cannot be rendered,
+    /// or is played through a bus.
+    fn try_play_dsp_with_settings(
         &mut self,
         assets: &mut Self::Assets,
         source: &DspSource,
         settings: Self::Settings,
-    ) -> Self::Sink {
-        let audio_source = OddioBackend::convert_to_audio_source(source.clone());
+    ) -> Result<Self::Sink, DspError> {
+        let audio_source = OddioBackend::try_convert_to_mono_audio_source(source.clone())?;
         let source_handle = assets.add(audio_source);
-        self.play(source_handle, settings)
+        Ok(self.play(source_handle, settings))
     }
 }
 
@@ -158,13 +224,15 @@ impl DspAudioExt for Audio<[f32; 2], DspSource> {
     type Settings = <DspSource as ToSignal>::Settings;
     type Sink = Handle<AudioSink<DspSource>>;
 
-    fn play_dsp_with_settings(
+    fn try_play_dsp_with_settings(
         &mut self,
         assets: &mut Self::Assets,
         source: &DspSource,
         settings: Self::Settings,
-    ) -> Self::Sink {
+    ) -> Result<Self::Sink, DspError> {
+        check_bus(source)?;
+
         let source_handle = assets.add(source.clone());
-        self.play(source_handle, settings)
+        Ok(self.play(source_handle, settings))
     }
 }
@@ -0,0 +1,355 @@
+//! Module for [`Controls`],
+//! the named parameters of a single DSP source.
+
+use {
+    bevy::utils::HashMap,
+    fundsp::hacker32::{shared, Shared},
+    std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        sync::{
+            mpsc::{self, Receiver, Sender},
+            Arc, Mutex, MutexGuard, PoisonError, Weak,
+        },
+    },
+};
+
+/// The named parameters of a single [`DspSource`](crate::dsp_source::DspSource).
+///
+/// Each DSP source from the [`DspManager`](crate::dsp_manager::DspManager)
+/// has its own controls, even if they come from the same DSP graph.
+/// Cloning the controls returns a handle to the same parameters,
+/// so they can be kept after playing the DSP source.
+///
+/// The DSP graph reads the parameters using [`Controls::shared`],
+/// see [`controllable`](crate::dsp_graph::controllable).
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// fn engine_hum(controls: &Controls) -> impl AudioUnit32 {
+///     var(&controls.shared("rpm", 1000.0)) * (1.0 / 60.0) >> saw() >> split::<U2>() * 0.2
+/// }
+///
+/// #[derive(Component)]
+/// struct Engine(Controls);
+///
+/// fn rev_engines(engines: Query<(&Engine, &Transform)>) {
+///     for (engine, transform) in &engines {
+///         engine.0.set("rpm", 1000.0 + transform.translation.length() * 100.0);
+///     }
+/// }
+/// ```
+///
+/// Parameters set with [`Controls::set`] take effect
+/// at the start of the next rendered block.
+/// Use [`Controls::set_at`] to change a parameter at an exact frame.
+///
+/// Static DSP sources are rendered once for each set of parameter values and cached,
+/// so playing two instances with different parameters renders both,
+/// while changing the parameters during playback has no effect.
+#[derive(Clone, Default)]
+pub struct Controls(Arc<ControlsInner>);
+
+#[derive(Default)]
+struct ControlsInner {
+    parameters: Mutex<HashMap<String, Shared<f32>>>,
+    /// The event queue of each playing instance,
+    /// with a handle that is dropped when the instance is dropped.
+    queues: Mutex<Vec<(Sender<ParamEvent>, Weak<()>)>>,
+    /// The events set while no instance is playing,
+    /// sent to the next instance that starts playing.
+    buffered: Mutex<Vec<BufferedEvent>>,
+}
+
+/// An event set with [`Controls::set_at`] while no instance is playing.
+struct BufferedEvent {
+    name: String,
+    value: f32,
+    frame: u64,
+}
+
+/// The event queue of a playing instance, returned by [`Controls::subscribe`].
+///
+/// The queue is removed from the controls
+/// the next time they are used after this is dropped.
+pub(crate) struct ParamEvents {
+    receiver: Receiver<ParamEvent>,
+    _alive: Arc<()>,
+}
+
+impl ParamEvents {
+    /// Receive the next event without blocking.
+    pub(crate) fn try_recv(&self) -> Option<ParamEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// A change of a parameter at an exact frame.
+pub(crate) struct ParamEvent {
+    /// The frame counted from the start of the playing instance.
+    pub(crate) frame: u64,
+    pub(crate) parameter: Shared<f32>,
+    pub(crate) value: f32,
+}
+
+impl Controls {
+    /// Create controls without parameters.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the shared variable of the given parameter.
+    ///
+    /// If the parameter does not exist yet,
+    /// it is created with the given value.
+    /// Otherwise, the given value is ignored.
+    ///
+    /// This is meant to be used when generating the DSP graph.
+    #[must_use]
+    pub fn shared(&self, name: &str, value: f32) -> Shared<f32> {
+        self.lock()
+            .entry(name.to_owned())
+            .or_insert_with(|| shared(value))
+            .clone()
+    }
+
+    /// Set the value of the given parameter.
+    ///
+    /// If the parameter does not exist yet,
+    /// it is created with the given value.
+    /// This allows setting parameters before the DSP source is played.
+    pub fn set(&self, name: &str, value: f32) {
+        let mut parameters = self.lock();
+
+        match parameters.get(name) {
+            Some(parameter) => parameter.set_value(value),
+            None => {
+                parameters.insert(name.to_owned(), shared(value));
+            }
+        }
+    }
+
+    /// Set the value of the given parameter at the given frame.
+    ///
+    /// The frame is counted from the first frame rendered
+    /// by each playing instance of the DSP source,
+    /// at the sample rate of the DSP source.
+    /// Frames that were already rendered take effect
+    /// at the start of the next rendered block.
+    ///
+    /// The events are sent through a channel,
+    /// so the audio thread receives them without locking,
+    /// and are applied at the exact frame,
+    /// independent of the block size of the DSP source.
+    ///
+    /// Events set while no instance is playing are kept
+    /// and sent to the next instance that starts playing.
+    /// Unlike [`Controls::set`], this never creates the parameter,
+    /// so events of parameters that the DSP graph does not read are ignored.
+    pub fn set_at(&self, name: &str, value: f32, frame: u64) {
+        let mut queues = lock(&self.0.queues);
+        queues.retain(|(_, alive)| alive.strong_count() > 0);
+
+        if queues.is_empty() {
+            lock(&self.0.buffered).push(BufferedEvent {
+                name: name.to_owned(),
+                value,
+                frame,
+            });
+            return;
+        }
+
+        let Some(parameter) = self.lock().get(name).cloned() else {
+            return;
+        };
+
+        queues.retain(|(queue, alive)| {
+            alive.strong_count() > 0
+                && queue
+                    .send(ParamEvent {
+                        frame,
+                        parameter: parameter.clone(),
+                        value,
+                    })
+                    // The instance stopped playing.
+                    .is_ok()
+        });
+    }
+
+    /// Get the value of the given parameter.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.lock().get(name).map(Shared::value)
+    }
+
+    /// A hash of the names and values of the parameters,
+    /// to tell renders with different parameters apart.
+    pub(crate) fn values_hash(&self) -> u64 {
+        let parameters = self.lock();
+        let mut values: Vec<(&str, u32)> = parameters
+            .iter()
+            .map(|(name, parameter)| (name.as_str(), parameter.value().to_bits()))
+            .collect();
+        values.sort_unstable();
+
+        let mut hasher = DefaultHasher::new();
+        values.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Create an event queue for a new playing instance.
+    ///
+    /// The queues of instances that were dropped are removed.
+    /// If no other instance is playing, the buffered events are sent to the new queue,
+    /// so this must be called after generating the DSP graph.
+    pub(crate) fn subscribe(&self) -> ParamEvents {
+        let (sender, receiver) = mpsc::channel();
+        let alive = Arc::new(());
+
+        let mut queues = lock(&self.0.queues);
+        queues.retain(|(_, alive)| alive.strong_count() > 0);
+
+        if queues.is_empty() {
+            let parameters = self.lock();
+
+            for event in lock(&self.0.buffered).drain(..) {
+                if let Some(parameter) = parameters.get(&event.name) {
+                    // The receiver is alive, so this cannot fail.
+                    let _ = sender.send(ParamEvent {
+                        frame: event.frame,
+                        parameter: parameter.clone(),
+                        value: event.value,
+                    });
+                }
+            }
+        }
+
+        queues.push((sender, Arc::downgrade(&alive)));
+
+        ParamEvents {
+            receiver,
+            _alive: alive,
+        }
+    }
+
+    /// The number of event queues of playing instances.
+    #[cfg(test)]
+    fn queue_count(&self) -> usize {
+        lock(&self.0.queues).len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Shared<f32>>> {
+        lock(&self.0.parameters)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports)]
+
+    use {
+        super::Controls,
+        crate::{
+            dsp_graph::controllable,
+            dsp_manager::DspManager,
+            dsp_source::{DspSource, SourceType},
+        },
+        fundsp::hacker32::*,
+    };
+
+    #[test]
+    fn controls_are_per_instance() {
+        let mut dsp_manager = DspManager::new(44100.0);
+
+        let level = controllable(|controls: &Controls| var(&controls.shared("level", 0.5)));
+
+        dsp_manager.add_graph(level, SourceType::Dynamic).unwrap();
+
+        let first = dsp_manager.get_graph(level).unwrap().with_block_size(1);
+        let second = dsp_manager.get_graph(level).unwrap().with_block_size(1);
+
+        let first_controls = first.controls().clone();
+        second.controls().set("level", 0.25);
+
+        let mut first = first.into_iter();
+        let mut second = second.into_iter();
+
+        assert_eq!(first.next(), Some([0.5, 0.5]));
+        assert_eq!(second.next(), Some([0.25, 0.25]));
+
+        first_controls.set("level", 1.0);
+
+        assert_eq!(first.next(), Some([1.0, 1.0]));
+        assert_eq!(second.next(), Some([0.25, 0.25]));
+        assert_eq!(first_controls.get("level"), Some(1.0));
+        assert_eq!(first_controls.get("missing"), None);
+    }
+
+    #[test]
+    fn sample_accurate_events() {
+        let level = controllable(|controls: &Controls| var(&controls.shared("level", 0.0)));
+        let dsp_source = DspSource::new(level, 44100.0, SourceType::Dynamic).with_block_size(64);
+
+        let controls = dsp_source.controls().clone();
+        let mut iter = dsp_source.into_iter();
+
+        controls.set_at("level", 1.0, 10);
+        controls.set_at("level", 0.5, 100);
+
+        let frames: Vec<f32> = iter.by_ref().take(128).map(|[left, _]| left).collect();
+
+        assert_eq!(frames[..10], [0.0; 10]);
+        assert_eq!(frames[10..100], [1.0; 90]);
+        assert_eq!(frames[100..], [0.5; 28]);
+
+        // Frames in the past are applied at the next block.
+        controls.set_at("level", 0.25, 0);
+
+        assert_eq!(iter.next(), Some([0.25, 0.25]));
+    }
+
+    #[test]
+    fn events_are_buffered_until_an_instance_plays() {
+        let level = controllable(|controls: &Controls| var(&controls.shared("level", 0.0)));
+        let dsp_source = DspSource::new(level, 44100.0, SourceType::Dynamic);
+        let controls = dsp_source.controls().clone();
+
+        controls.set_at("level", 1.0, 10);
+
+        // The parameter keeps the value of the DSP graph until the event.
+        assert_eq!(controls.get("level"), None);
+
+        let mut iter = dsp_source.into_iter();
+        let frames: Vec<f32> = iter.by_ref().take(20).map(|[left, _]| left).collect();
+
+        assert_eq!(frames[..10], [0.0; 10]);
+        assert_eq!(frames[10..], [1.0; 10]);
+
+        // Events of parameters the DSP graph does not read are ignored.
+        controls.set_at("missing", 1.0, 0);
+
+        assert_eq!(controls.get("missing"), None);
+        assert_eq!(iter.next(), Some([1.0, 1.0]));
+    }
+
+    #[test]
+    fn dropped_instances_are_unsubscribed() {
+        let level = controllable(|controls: &Controls| var(&controls.shared("level", 0.0)));
+        let dsp_source = DspSource::new(level, 44100.0, SourceType::Dynamic);
+        let controls = dsp_source.controls().clone();
+
+        for _ in 0..10 {
+            drop(dsp_source.clone().into_iter());
+        }
+
+        let _playing = dsp_source.clone().into_iter();
+
+        assert_eq!(controls.queue_count(), 1);
+    }
+}
@@ -0,0 +1,414 @@
+//! Module for [`DspBus`] and [`DspBuses`],
+//! which route playing DSP sources through submixes.
+
+use {
+    bevy::{prelude::Resource, utils::HashMap},
+    fundsp::hacker32::{shared, AudioUnit32, Shared},
+    std::{borrow::Cow, sync::Arc},
+};
+
+/// The name of a bus that DSP sources are played through.
+///
+/// DSP sources are played through the [`DspBus::MASTER`] bus by default,
+/// see [`DspSource::with_bus`](crate::dsp_source::DspSource::with_bus).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DspBus(Cow<'static, str>);
+
+impl DspBus {
+    /// The bus that every other bus feeds into by default.
+    /// Its output is the output of the backend.
+    pub const MASTER: Self = Self::new("master");
+    /// The bus for music.
+    pub const MUSIC: Self = Self::new("music");
+    /// The bus for sound effects.
+    pub const SFX: Self = Self::new("sfx");
+    /// The bus for user interface sounds.
+    pub const UI: Self = Self::new("ui");
+    /// The bus for voices and dialogue.
+    pub const VOICE: Self = Self::new("voice");
+
+    /// Create a bus with the given name.
+    #[must_use]
+    pub const fn new(name: &'static str) -> Self {
+        Self(Cow::Borrowed(name))
+    }
+
+    /// Create a bus with the given name, created at runtime.
+    #[must_use]
+    pub fn from_string(name: String) -> Self {
+        Self(Cow::Owned(name))
+    }
+
+    /// The name of the bus.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The master bus, in a static so it can be borrowed.
+static MASTER: DspBus = DspBus::MASTER;
+
+type EffectFn = dyn Fn() -> Box<dyn AudioUnit32> + Send + Sync;
+
+/// The gain, effect and output of a [`DspBus`].
+///
+/// Clones of the settings share the gain.
+#[derive(Clone)]
+pub struct BusSettings {
+    gain: Shared<f32>,
+    effect: Option<Arc<EffectFn>>,
+    output: DspBus,
+}
+
+impl Default for BusSettings {
+    fn default() -> Self {
+        Self {
+            gain: shared(1.0),
+            effect: None,
+            output: DspBus::MASTER,
+        }
+    }
+}
+
+impl BusSettings {
+    /// Create settings with a gain of 1, no effect,
+    /// and the [`DspBus::MASTER`] bus as the output.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the gain as an amplitude, where 1 is the original volume.
+    #[must_use]
+    pub fn with_gain(self, gain: f32) -> Self {
+        self.set_gain(gain);
+        self
+    }
+
+    /// Apply the given effect to the mixed DSP sources of the bus,
+    /// before the gain.
+    ///
+    /// The effect is generated for each backend output, like a DSP graph.
+    ///
+    /// ```no_run
+    /// # use bevy_fundsp::prelude::*;
+    /// let music = BusSettings::new().with_effect(|| reverb_stereo(20.0, 2.0));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the effect does not have two inputs and two outputs.
+    #[must_use]
+    pub fn with_effect<F, U>(mut self, effect: F) -> Self
+    where
+        F: Fn() -> U + Send + Sync + 'static,
+        U: AudioUnit32 + 'static,
+    {
+        let unit = effect();
+        assert!(
+            unit.inputs() == 2 && unit.outputs() == 2,
+            "The effect of a bus must have two inputs and two outputs."
+        );
+
+        self.effect = Some(Arc::new(move || Box::new(effect()) as Box<dyn AudioUnit32>));
+        self
+    }
+
+    /// Set the bus that this bus feeds into.
+    ///
+    /// By default, this is the [`DspBus::MASTER`] bus.
+    #[must_use]
+    pub fn with_output(mut self, output: DspBus) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// The gain as an amplitude.
+    #[must_use]
+    pub fn gain(&self) -> f32 {
+        self.gain.value()
+    }
+
+    /// Set the gain as an amplitude.
+    ///
+    /// This takes effect while the DSP sources are playing.
+    pub fn set_gain(&self, gain: f32) {
+        self.gain.set_value(gain.max(0.0));
+    }
+
+    /// The bus that this bus feeds into.
+    #[must_use]
+    pub fn output(&self) -> &DspBus {
+        &self.output
+    }
+
+    pub(crate) fn shared_gain(&self) -> Shared<f32> {
+        self.gain.clone()
+    }
+
+    pub(crate) fn generate_effect(&self) -> Option<Box<dyn AudioUnit32>> {
+        self.effect.as_ref().map(|effect| effect())
+    }
+}
+
+/// The buses that DSP sources are played through.
+///
+/// By default, the [`DspBus::MUSIC`], [`DspBus::SFX`], [`DspBus::UI`]
+/// and [`DspBus::VOICE`] buses feed into the [`DspBus::MASTER`] bus.
+/// Buses can feed into any other bus,
+/// such as a `footsteps` bus feeding into the sound effects.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// let buses = DspBuses::default()
+///     .with_bus(DspBus::MUSIC, BusSettings::new().with_gain(0.5))
+///     .with_bus(
+///         DspBus::new("footsteps"),
+///         BusSettings::new().with_output(DspBus::SFX),
+///     );
+///
+/// App::new()
+///     .insert_resource(buses)
+///     .add_plugins(DspPlugin::default());
+///
+/// fn mute_music(buses: Res<DspBuses>) {
+///     buses.set_gain(&DspBus::MUSIC, 0.0);
+/// }
+/// ```
+///
+/// The buses are read by the backend when the [`DspPlugin`](crate::DspPlugin) is built,
+/// so they should be inserted before it.
+/// The gains can be changed at any time.
+///
+/// The buses are mixed by the backends that use a [`Mixer`](crate::mixer::Mixer).
+/// With `kira`, they are only supported for dynamic DSP sources
+/// played with your own kira `AudioManager` through `KiraBuses`.
+/// DSP sources played on a `bevy_kira_audio` channel play on that channel instead.
+///
+/// `bevy_audio` has no submixes, so only the gains of the buses are applied,
+/// to the volume of the entities spawned with
+/// [`DspCommandsExt`](crate::backend::bevy_audio::DspCommandsExt).
+/// The effects of the buses are not applied.
+/// `oddio` cannot apply buses at all,
+/// so converting a DSP source played through a bus other than the master bus fails
+/// with [`DspError::UnsupportedBus`](crate::dsp_error::DspError::UnsupportedBus).
+///
+/// Buses that feed into a missing bus, or into themselves through other buses,
+/// feed into the [`DspBus::MASTER`] bus instead.
+/// DSP sources played through a missing bus are played through the master bus.
+#[derive(Resource, Clone)]
+pub struct DspBuses {
+    buses: HashMap<DspBus, BusSettings>,
+}
+
+impl Default for DspBuses {
+    fn default() -> Self {
+        let buses = [
+            DspBus::MASTER,
+            DspBus::MUSIC,
+            DspBus::SFX,
+            DspBus::UI,
+            DspBus::VOICE,
+        ]
+        .into_iter()
+        .map(|bus| (bus, BusSettings::default()))
+        .collect();
+
+        Self { buses }
+    }
+}
+
+impl DspBuses {
+    /// Add the given bus, or replace its settings.
+    #[must_use]
+    pub fn with_bus(mut self, bus: DspBus, settings: BusSettings) -> Self {
+        self.buses.insert(bus, settings);
+        self
+    }
+
+    /// Get the settings of the given bus.
+    #[must_use]
+    pub fn get(&self, bus: &DspBus) -> Option<&BusSettings> {
+        self.buses.get(bus)
+    }
+
+    /// Set the gain of the given bus, if it exists.
+    pub fn set_gain(&self, bus: &DspBus, gain: f32) {
+        if let Some(settings) = self.get(bus) {
+            settings.set_gain(gain);
+        }
+    }
+
+    /// The buses ordered so that each bus comes before the bus it feeds into,
+    /// with the index of that bus.
+    ///
+    /// The master bus is always last, and feeds into nothing.
+    pub(crate) fn routing<'a>(&'a self) -> Vec<(DspBus, BusSettings, Option<usize>)> {
+        let outputs: HashMap<&'a DspBus, &'a DspBus> = self
+            .buses
+            .keys()
+            .filter(|&bus| *bus != DspBus::MASTER)
+            .map(|bus| {
+                let output = if self.is_in_cycle(bus) {
+                    &MASTER
+                } else {
+                    self.output_of(bus)
+                };
+
+                (bus, output)
+            })
+            .collect();
+
+        let depth = |mut bus: &'a DspBus| {
+            let mut depth = 0;
+
+            while let Some(output) = outputs.get(bus) {
+                bus = *output;
+                depth += 1;
+            }
+
+            depth
+        };
+
+        let mut order: Vec<&DspBus> = self.buses.keys().collect();
+        order.sort_by(|a, b| depth(b).cmp(&depth(a)).then_with(|| a.name().cmp(b.name())));
+
+        order
+            .iter()
+            .map(|&bus| {
+                let output = outputs
+                    .get(bus)
+                    .and_then(|output| order.iter().position(|bus| bus == output));
+
+                (bus.clone(), self.buses[bus].clone(), output)
+            })
+            .collect()
+    }
+
+    /// The gains of the given bus and of every bus it feeds into,
+    /// down to the master bus.
+    ///
+    /// Like [`DspBuses::routing`], a missing bus is the master bus,
+    /// and buses in a cycle feed into the master bus.
+    #[cfg_attr(not(feature = "bevy_audio"), allow(dead_code))]
+    pub(crate) fn gains(&self, bus: &DspBus) -> Vec<Shared<f32>> {
+        let mut bus = if self.buses.contains_key(bus) {
+            bus
+        } else {
+            &MASTER
+        };
+        let mut gains = vec![self.buses[bus].shared_gain()];
+
+        while *bus != DspBus::MASTER {
+            bus = if self.is_in_cycle(bus) {
+                &MASTER
+            } else {
+                self.output_of(bus)
+            };
+            gains.push(self.buses[bus].shared_gain());
+        }
+
+        gains
+    }
+
+    /// The bus that the given bus feeds into,
+    /// or the master bus if that bus is missing.
+    fn output_of(&self, bus: &DspBus) -> &DspBus {
+        match self.buses.get(bus) {
+            Some(settings) if self.buses.contains_key(&settings.output) => &settings.output,
+            _ => &MASTER,
+        }
+    }
+
+    fn is_in_cycle(&self, bus: &DspBus) -> bool {
+        let mut current = self.output_of(bus);
+
+        for _ in 0..self.buses.len() {
+            if current == bus {
+                return true;
+            }
+
+            if *current == DspBus::MASTER {
+                return false;
+            }
+
+            current = self.output_of(current);
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{BusSettings, DspBus, DspBuses},
+        fundsp::hacker32::Shared,
+    };
+
+    #[test]
+    fn buses_are_routed_before_their_output() {
+        let footsteps = DspBus::new("footsteps");
+        let left = DspBus::new("left");
+        let right = DspBus::new("right");
+
+        let buses = DspBuses::default()
+            .with_bus(
+                footsteps.clone(),
+                BusSettings::new().with_output(DspBus::SFX),
+            )
+            .with_bus(left.clone(), BusSettings::new().with_output(right.clone()))
+            .with_bus(right.clone(), BusSettings::new().with_output(left.clone()))
+            .with_bus(
+                DspBus::UI,
+                BusSettings::new().with_output(DspBus::new("missing")),
+            );
+
+        let routing = buses.routing();
+        let index = |bus: &DspBus| routing.iter().position(|(other, ..)| other == bus).unwrap();
+        let output = |bus: &DspBus| {
+            routing[index(bus)]
+                .2
+                .map(|output| routing[output].0.clone())
+        };
+
+        assert_eq!(routing.len(), 8);
+        assert_eq!(routing.last().unwrap().0, DspBus::MASTER);
+        assert_eq!(output(&DspBus::MASTER), None);
+        assert_eq!(output(&footsteps), Some(DspBus::SFX));
+        assert!(index(&footsteps) < index(&DspBus::SFX));
+        assert_eq!(output(&left), Some(DspBus::MASTER));
+        assert_eq!(output(&right), Some(DspBus::MASTER));
+        assert_eq!(output(&DspBus::UI), Some(DspBus::MASTER));
+    }
+
+    #[test]
+    fn gains_follow_the_outputs_of_buses() {
+        let footsteps = DspBus::new("footsteps");
+        let left = DspBus::new("left");
+
+        let buses = DspBuses::default()
+            .with_bus(DspBus::MASTER, BusSettings::new().with_gain(0.5))
+            .with_bus(DspBus::SFX, BusSettings::new().with_gain(0.25))
+            .with_bus(
+                footsteps.clone(),
+                BusSettings::new().with_gain(2.0).with_output(DspBus::SFX),
+            )
+            .with_bus(left.clone(), BusSettings::new().with_output(left.clone()));
+
+        let gains = |bus: &DspBus| {
+            buses
+                .gains(bus)
+                .iter()
+                .map(Shared::value)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(gains(&footsteps), [2.0, 0.25, 0.5]);
+        assert_eq!(gains(&DspBus::MASTER), [0.5]);
+        assert_eq!(gains(&DspBus::new("missing")), [0.5]);
+        assert_eq!(gains(&left), [1.0, 0.5]);
+    }
+}
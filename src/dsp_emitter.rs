@@ -0,0 +1,261 @@
+//! Module for [`DspEmitter`],
+//! which positions dynamic DSP sources relative to a [`DspListener`].
+
+use {
+    bevy::prelude::{Component, GlobalTransform, Query, With},
+    std::{
+        f32::consts::{FRAC_1_SQRT_2, FRAC_PI_4},
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+    },
+};
+
+/// Pans and attenuates the DSP sources played with it,
+/// based on the [`GlobalTransform`] of its entity
+/// relative to the entity with the [`DspListener`].
+///
+/// The panning and attenuation are applied when rendering the DSP source,
+/// instead of using the spatial audio of the backend,
+/// so they work the same on every backend.
+/// This uses equal power panning, without delays or filtering.
+///
+/// Only dynamic DSP sources are positioned,
+/// and DSP sources played in mono are only attenuated,
+/// see [`DspSource::with_emitter`](crate::dsp_source::DspSource::with_emitter).
+/// Static DSP sources are played without panning or attenuation.
+/// Clones of the emitter share its position.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// # fn engine_hum() -> impl AudioUnit32 { saw_hz(50.0) }
+/// fn spawn_car(mut commands: Commands, dsp_manager: Res<DspManager>) {
+///     let emitter = DspEmitter::new().with_reference_distance(2.0);
+///
+///     if let Some(dsp_source) = dsp_manager.get_graph(engine_hum) {
+///         let dsp_source = dsp_source.with_emitter(&emitter);
+///         // Play the DSP source using the backend...
+///     }
+///
+///     commands.spawn((emitter, TransformBundle::default()));
+/// }
+///
+/// fn spawn_camera(mut commands: Commands) {
+///     commands.spawn((Camera3dBundle::default(), DspListener));
+/// }
+/// ```
+#[derive(Component, Clone)]
+pub struct DspEmitter {
+    gains: Arc<SpatialGains>,
+    reference_distance: f32,
+    rolloff: f32,
+}
+
+/// Marks the entity the [`DspEmitter`]s are heard from.
+///
+/// If there are multiple listeners, only one of them is used.
+/// If there is none, the emitters keep their last panning and attenuation.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct DspListener;
+
+/// The gains of the left and right channels of a [`DspEmitter`].
+///
+/// Until the emitter is first updated, these are the gains of a centered,
+/// unattenuated emitter, so DSP sources do not jump in loudness.
+pub(crate) struct SpatialGains([AtomicU32; 2]);
+
+impl Default for SpatialGains {
+    fn default() -> Self {
+        Self([
+            AtomicU32::new(FRAC_1_SQRT_2.to_bits()),
+            AtomicU32::new(FRAC_1_SQRT_2.to_bits()),
+        ])
+    }
+}
+
+impl SpatialGains {
+    pub(crate) fn get(&self) -> [f32; 2] {
+        let [left, right] = &self.0;
+
+        [left, right].map(|gain| f32::from_bits(gain.load(Ordering::Relaxed)))
+    }
+
+    fn set(&self, gains: [f32; 2]) {
+        for (gain, value) in self.0.iter().zip(gains) {
+            gain.store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for DspEmitter {
+    fn default() -> Self {
+        Self {
+            gains: Arc::default(),
+            reference_distance: 1.0,
+            rolloff: 1.0,
+        }
+    }
+}
+
+impl DspEmitter {
+    /// Create an emitter with a reference distance and rolloff of 1.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the distance under which the DSP sources are not attenuated.
+    ///
+    /// By default, this is 1.
+    #[must_use]
+    pub fn with_reference_distance(mut self, reference_distance: f32) -> Self {
+        self.reference_distance = reference_distance.max(f32::EPSILON);
+        self
+    }
+
+    /// Set how fast the DSP sources are attenuated
+    /// past the reference distance.
+    ///
+    /// The gain is `reference / (reference + rolloff * (distance - reference))`.
+    /// By default, this is 1, so doubling the distance halves the gain.
+    /// A rolloff of 0 disables the attenuation.
+    #[must_use]
+    pub fn with_rolloff(mut self, rolloff: f32) -> Self {
+        self.rolloff = rolloff.max(0.0);
+        self
+    }
+
+    pub(crate) fn gains(&self) -> Arc<SpatialGains> {
+        self.gains.clone()
+    }
+
+    /// Compute the gains of the left and right channels
+    /// given the translation relative to the listener,
+    /// where positive X is to the right of the listener.
+    fn compute_gains(&self, x: f32, distance: f32) -> [f32; 2] {
+        let reference = self.reference_distance;
+        let attenuation =
+            reference / (reference + self.rolloff * (distance.max(reference) - reference));
+
+        let pan = if distance > f32::EPSILON {
+            (x / distance).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+
+        // Equal power panning, so the loudness is the same in every direction.
+        let angle = (pan + 1.0) * FRAC_PI_4;
+
+        [angle.cos() * attenuation, angle.sin() * attenuation]
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn update_dsp_emitters(
+    listeners: Query<&GlobalTransform, With<DspListener>>,
+    emitters: Query<(&DspEmitter, &GlobalTransform)>,
+) {
+    let Some(listener) = listeners.iter().next() else {
+        return;
+    };
+
+    let (_, rotation, translation) = listener.to_scale_rotation_translation();
+    let inverse_rotation = rotation.inverse();
+
+    for (emitter, transform) in &emitters {
+        let relative = inverse_rotation * (transform.translation() - translation);
+
+        emitter
+            .gains
+            .set(emitter.compute_gains(relative.x, relative.length()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::float_cmp)]
+
+    use {
+        super::{update_dsp_emitters, DspEmitter, DspListener},
+        crate::dsp_source::{Channels, DspSource, SourceType},
+        bevy::prelude::{App, GlobalTransform, Transform, Update, Vec3},
+        fundsp::hacker32::constant,
+    };
+
+    fn gains(emitter: &DspEmitter) -> [f32; 2] {
+        emitter.gains().get()
+    }
+
+    #[test]
+    fn emitters_are_panned_and_attenuated() {
+        let mut app = App::new();
+        app.add_systems(Update, update_dsp_emitters);
+
+        let left = DspEmitter::new();
+        let far = DspEmitter::new();
+        let unattenuated = DspEmitter::new().with_rolloff(0.0);
+
+        app.world.spawn((DspListener, GlobalTransform::IDENTITY));
+        app.world.spawn((
+            left.clone(),
+            GlobalTransform::from(Transform::from_xyz(-1.0, 0.0, 0.0)),
+        ));
+        app.world.spawn((
+            far.clone(),
+            GlobalTransform::from(Transform::from_xyz(0.0, 0.0, -4.0)),
+        ));
+        app.world.spawn((
+            unattenuated.clone(),
+            GlobalTransform::from(Transform::from_translation(Vec3::splat(100.0))),
+        ));
+
+        // Emitters start centered.
+        assert_eq!(gains(&left), [std::f32::consts::FRAC_1_SQRT_2; 2]);
+
+        app.update();
+
+        let [l, r] = gains(&left);
+        assert!((l - 1.0).abs() < 1e-6 && r.abs() < 1e-6);
+
+        let [l, r] = gains(&far);
+        assert!((l - r).abs() < 1e-6);
+        assert!((l * l + r * r - 0.25 * 0.25).abs() < 1e-6);
+
+        let [l, r] = gains(&unattenuated);
+        assert!(l > 0.0 && r > l);
+        assert!((l * l + r * r - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn emitter_gains_are_applied() {
+        let emitter = DspEmitter::new();
+        emitter.gains.set([0.5, 0.25]);
+
+        let mut iter = DspSource::new(|| constant(1.0), 44100.0, SourceType::Dynamic)
+            .with_emitter(&emitter)
+            .into_iter();
+
+        assert_eq!(iter.next(), Some([0.5, 0.25]));
+    }
+
+    #[test]
+    fn mono_sources_are_only_attenuated() {
+        let emitter = DspEmitter::new();
+        let mono = || {
+            DspSource::new(|| constant(1.0), 44100.0, SourceType::Dynamic)
+                .with_channels(Channels::Mono)
+                .with_emitter(&emitter)
+                .into_iter()
+                .into_mono()
+        };
+
+        // Centered emitters are not attenuated.
+        assert!((mono().next().unwrap() - 1.0).abs() < 1e-6);
+
+        emitter.gains.set([0.5, 0.0]);
+
+        assert!((mono().next().unwrap() - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,85 @@
+//! Module for [`DspError`].
+
+use {
+    crate::dsp_bus::DspBus,
+    std::{
+        error::Error,
+        fmt::{Display, Formatter},
+    },
+    uuid::Uuid,
+};
+
+/// Errors that can occur when using DSP sources.
+#[derive(Debug)]
+pub enum DspError {
+    /// The DSP source is dynamic,
+    /// so it has no definite length.
+    ///
+    /// This happens when trying to convert a dynamic DSP source
+    /// into a static audio source.
+    DynamicSource,
+    /// No DSP source is registered with the given ID.
+    GraphNotFound(Uuid),
+    /// A DSP source is already registered with the given ID.
+    ///
+    /// See [`DspGraph::named`](crate::dsp_graph::DspGraph::named)
+    /// to give DSP graphs distinct IDs.
+    DuplicateGraph(Uuid),
+    /// The parameterized DSP source with the given ID
+    /// is generated with a different parameter type.
+    ParamMismatch(Uuid),
+    /// The rendered DSP source cannot be written.
+    Io(std::io::Error),
+    /// The backend cannot decode the rendered DSP source.
+    Decode(Box<dyn Error + Send + Sync>),
+    /// The backend cannot play DSP sources through the given bus.
+    ///
+    /// See [`DspBuses`](crate::dsp_bus::DspBuses) for the buses supported by each backend.
+    UnsupportedBus(DspBus),
+}
+
+impl Display for DspError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DspError::DynamicSource => write!(
+                f,
+                "Only static DSP sources can be converted into static audio sources."
+            ),
+            DspError::GraphNotFound(uuid) => write!(f, "DSP source not found! ID: {uuid}"),
+            DspError::DuplicateGraph(uuid) => {
+                write!(f, "DSP source already registered! ID: {uuid}")
+            }
+            DspError::ParamMismatch(uuid) => write!(
+                f,
+                "DSP source is generated with a different parameter type! ID: {uuid}"
+            ),
+            DspError::Io(err) => write!(f, "Cannot write DSP source. Error: {err}"),
+            DspError::Decode(err) => write!(f, "Cannot read DSP source. Error: {err}"),
+            DspError::UnsupportedBus(bus) => write!(
+                f,
+                "DSP sources cannot be played through a bus by this backend! Bus: {}",
+                bus.name()
+            ),
+        }
+    }
+}
+
+impl Error for DspError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DspError::DynamicSource
+            | DspError::GraphNotFound(_)
+            | DspError::DuplicateGraph(_)
+            | DspError::ParamMismatch(_)
+            | DspError::UnsupportedBus(_) => None,
+            DspError::Io(err) => Some(err),
+            DspError::Decode(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl From<std::io::Error> for DspError {
+    fn from(err: std::io::Error) -> Self {
+        DspError::Io(err)
+    }
+}
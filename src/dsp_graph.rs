@@ -1,6 +1,16 @@
-//! Module for the [`DspGraph`] trait.
+//! Module for the [`DspGraph`] and [`ParamDspGraph`] traits.
 
-use {fundsp::prelude::AudioUnit32, uuid::Uuid};
+use {
+    crate::controls::Controls,
+    fundsp::prelude::AudioUnit32,
+    std::{
+        any::TypeId,
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        sync::Arc,
+    },
+    uuid::Uuid,
+};
 
 /// Trait for generating DSP sources.
 ///
@@ -10,7 +20,11 @@ use {fundsp::prelude::AudioUnit32, uuid::Uuid};
 /// that implement `Fn() -> impl AudioUnit32`.
 ///
 /// If parameterless functions isn't enough for your usecase,
-/// you can implement your own custom type.
+/// see [`ParamDspGraph`], or implement your own custom type.
+///
+/// The ID of a function is derived from its [`TypeId`],
+/// so each function and closure has its own ID, but it changes between builds.
+/// Use [`DspGraph::named`] if the ID must be stable across builds.
 pub trait DspGraph: Send + Sync + 'static {
     /// The ID of the given graph.
     ///
@@ -24,6 +38,90 @@ pub trait DspGraph: Send + Sync + 'static {
 
     /// Generate a DSP graph.
     fn generate_graph(&self) -> Box<dyn AudioUnit32>;
+
+    /// Generate a DSP graph that reads its parameters from the given controls.
+    ///
+    /// By default, this ignores the controls and calls [`DspGraph::generate_graph`].
+    /// See [`controllable`].
+    fn generate_graph_with_controls(&self, controls: &Controls) -> Box<dyn AudioUnit32> {
+        let _ = controls;
+        self.generate_graph()
+    }
+
+    /// Give the DSP graph an ID derived from the given name.
+    ///
+    /// Unlike the ID of functions,
+    /// this is stable across builds and compiler versions,
+    /// so it can be stored in scenes and save files.
+    ///
+    /// ```no_run
+    /// # use bevy::prelude::*;
+    /// # use bevy_fundsp::prelude::*;
+    /// App::new()
+    ///     .add_plugins(DefaultPlugins)
+    ///     .add_plugins(DspPlugin::default())
+    ///     .add_dsp_source(white_noise.named("white_noise"), SourceType::Dynamic)
+    ///     .run();
+    ///
+    /// fn white_noise() -> impl AudioUnit32 {
+    ///     white() >> split::<U2>() * 0.2
+    /// }
+    /// ```
+    fn named(self, name: &str) -> Named<Self>
+    where
+        Self: Sized,
+    {
+        Named {
+            id: named_graph_id(name),
+            dsp_graph: self,
+        }
+    }
+}
+
+/// A DSP graph whose ID is derived from a name.
+///
+/// See [`DspGraph::named`].
+pub struct Named<D> {
+    id: Uuid,
+    dsp_graph: D,
+}
+
+/// The namespace of the IDs of [`Named`] DSP graphs.
+pub const NAMED_GRAPH_NAMESPACE: Uuid = Uuid::from_u128(0x5c2e_8f1a_3b7d_4e69_a0c4_d81f_62b9_e357);
+
+/// The ID of a DSP graph with the given name.
+///
+/// Useful for getting a DSP source with
+/// [`DspManager::get_graph_by_id`](crate::dsp_manager::DspManager::get_graph_by_id)
+/// without access to the DSP graph itself.
+#[must_use]
+pub fn named_graph_id(name: &str) -> Uuid {
+    Uuid::new_v5(&NAMED_GRAPH_NAMESPACE, name.as_bytes())
+}
+
+/// The ID of a function or closure, derived from its [`TypeId`].
+///
+/// Every closure has its own type, even when defined in the same function,
+/// so this is unique for each of them.
+/// `TypeId` cannot be converted into a UUID, so its hash is used instead.
+fn type_id_graph_id<F: 'static>() -> Uuid {
+    let mut hasher = DefaultHasher::new();
+    TypeId::of::<F>().hash(&mut hasher);
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, &hasher.finish().to_le_bytes())
+}
+
+impl<D: DspGraph> DspGraph for Named<D> {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn generate_graph(&self) -> Box<dyn AudioUnit32> {
+        self.dsp_graph.generate_graph()
+    }
+
+    fn generate_graph_with_controls(&self, controls: &Controls) -> Box<dyn AudioUnit32> {
+        self.dsp_graph.generate_graph_with_controls(controls)
+    }
 }
 
 impl<F, Au> DspGraph for F
@@ -32,11 +130,190 @@ where
     Au: AudioUnit32 + 'static,
 {
     fn id(&self) -> Uuid {
-        // TODO: This should be based on its `TypeId`
-        Uuid::new_v5(&Uuid::NAMESPACE_OID, std::any::type_name::<F>().as_bytes())
+        type_id_graph_id::<F>()
     }
 
     fn generate_graph(&self) -> Box<dyn AudioUnit32> {
         Box::new(self())
     }
 }
+
+/// Create a DSP graph that reads its parameters from the [`Controls`]
+/// of each DSP source.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// fn engine_hum(controls: &Controls) -> impl AudioUnit32 {
+///     var(&controls.shared("rpm", 1000.0)) * (1.0 / 60.0) >> saw() >> split::<U2>() * 0.2
+/// }
+///
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugins(DspPlugin::default())
+///     .add_dsp_source(controllable(engine_hum), SourceType::Dynamic)
+///     .run();
+/// ```
+pub fn controllable<F, Au>(generate_graph: F) -> Controllable<F>
+where
+    F: Send + Sync + 'static + Fn(&Controls) -> Au,
+    Au: AudioUnit32 + 'static,
+{
+    Controllable(generate_graph)
+}
+
+/// A DSP graph that reads its parameters from [`Controls`].
+///
+/// See [`controllable`].
+#[derive(Clone, Copy)]
+pub struct Controllable<F>(F);
+
+impl<F, Au> DspGraph for Controllable<F>
+where
+    F: Send + Sync + 'static + Fn(&Controls) -> Au,
+    Au: AudioUnit32 + 'static,
+{
+    fn id(&self) -> Uuid {
+        type_id_graph_id::<F>()
+    }
+
+    fn generate_graph(&self) -> Box<dyn AudioUnit32> {
+        self.generate_graph_with_controls(&Controls::new())
+    }
+
+    fn generate_graph_with_controls(&self, controls: &Controls) -> Box<dyn AudioUnit32> {
+        Box::new((self.0)(controls))
+    }
+}
+
+/// Trait for generating DSP sources from a parameter.
+///
+/// This is similar to [`DspGraph`],
+/// but each DSP source is generated with a value of type `P`.
+/// This allows a single registered definition
+/// to play many differently configured instances.
+///
+/// This is implemented for functions
+/// that implement `Fn(&P) -> impl AudioUnit32`.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// struct Synth {
+///     frequency: f32,
+///     cutoff: f32,
+/// }
+///
+/// fn synth(params: &Synth) -> impl AudioUnit32 {
+///     saw_hz(params.frequency) >> lowpass_hz(params.cutoff, 1.0) >> split::<U2>() * 0.2
+/// }
+///
+/// App::new()
+///     .add_plugins(DefaultPlugins)
+///     .add_plugins(DspPlugin::default())
+///     .add_param_dsp_source(synth, SourceType::Dynamic)
+///     .add_systems(Startup, play_synth)
+///     .run();
+///
+/// fn play_synth(dsp_manager: Res<DspManager>) {
+///     let low = dsp_manager.try_get_param_graph(synth, Synth { frequency: 110.0, cutoff: 400.0 });
+///     let high = dsp_manager.try_get_param_graph(synth, Synth { frequency: 440.0, cutoff: 2000.0 });
+///     // Play both DSP sources...
+/// }
+/// ```
+pub trait ParamDspGraph<P>: Send + Sync + 'static {
+    /// The ID of the given graph.
+    ///
+    /// See [`DspGraph::id`].
+    fn id(&self) -> Uuid;
+
+    /// Generate a DSP graph with the given parameter.
+    fn generate_graph(&self, params: &P) -> Box<dyn AudioUnit32>;
+
+    /// Give the DSP graph an ID derived from the given name.
+    ///
+    /// See [`DspGraph::named`].
+    fn named(self, name: &str) -> Named<Self>
+    where
+        Self: Sized,
+    {
+        Named {
+            id: named_graph_id(name),
+            dsp_graph: self,
+        }
+    }
+}
+
+impl<D: ParamDspGraph<P>, P> ParamDspGraph<P> for Named<D> {
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn generate_graph(&self, params: &P) -> Box<dyn AudioUnit32> {
+        self.dsp_graph.generate_graph(params)
+    }
+}
+
+impl<F, P, Au> ParamDspGraph<P> for F
+where
+    F: Send + Sync + 'static + Fn(&P) -> Au,
+    Au: AudioUnit32 + 'static,
+{
+    fn id(&self) -> Uuid {
+        type_id_graph_id::<F>()
+    }
+
+    fn generate_graph(&self, params: &P) -> Box<dyn AudioUnit32> {
+        Box::new(self(params))
+    }
+}
+
+/// A parameterized DSP graph bound to its parameter.
+pub(crate) struct WithParams<P> {
+    pub(crate) dsp_graph: Arc<dyn ParamDspGraph<P>>,
+    pub(crate) params: P,
+}
+
+impl<P: Send + Sync + 'static> DspGraph for WithParams<P> {
+    fn id(&self) -> Uuid {
+        self.dsp_graph.id()
+    }
+
+    fn generate_graph(&self) -> Box<dyn AudioUnit32> {
+        self.dsp_graph.generate_graph(&self.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports)]
+
+    use {
+        super::{named_graph_id, DspGraph, NAMED_GRAPH_NAMESPACE},
+        fundsp::hacker32::*,
+        uuid::Uuid,
+    };
+
+    #[test]
+    fn named_id_is_stable() {
+        let sine = || sine_hz(440.0);
+        let named_sine = sine.named("sine");
+
+        assert_eq!(named_sine.id(), named_graph_id("sine"));
+        assert_eq!(
+            named_sine.id(),
+            Uuid::new_v5(&NAMED_GRAPH_NAMESPACE, b"sine")
+        );
+        assert_ne!(named_sine.id(), sine.id());
+        assert_ne!(named_sine.id(), named_graph_id("square"));
+    }
+
+    #[test]
+    fn closures_have_different_ids() {
+        let white_noise = || white();
+        let pink_noise = || pink();
+
+        assert_ne!(white_noise.id(), pink_noise.id());
+        assert_eq!(white_noise.id(), white_noise.id());
+    }
+}
@@ -2,14 +2,18 @@
 
 use {
     crate::{
-        dsp_graph::DspGraph,
+        audio_clock::AudioClock,
+        dsp_error::DspError,
+        dsp_graph::{DspGraph, ParamDspGraph, WithParams},
         dsp_source::{DspSource, SourceType},
+        render_cache::RenderCache,
         DEFAULT_SAMPLE_RATE,
     },
     bevy::{
         prelude::{default, Resource},
         utils::HashMap,
     },
+    std::{any::Any, path::Path, sync::Arc},
     uuid::Uuid,
 };
 
@@ -20,7 +24,17 @@ use {
 #[derive(Resource)]
 pub struct DspManager {
     collection: HashMap<Uuid, DspSource>,
+    param_collection: HashMap<Uuid, ParamSource>,
     sample_rate: f32,
+    cache: Arc<RenderCache>,
+    audio_clock: AudioClock,
+}
+
+/// A registered [`ParamDspGraph`].
+struct ParamSource {
+    /// The type erased `Arc<dyn ParamDspGraph<P>>`.
+    dsp_graph: Box<dyn Any + Send + Sync>,
+    source_type: SourceType,
 }
 
 impl Default for DspManager {
@@ -34,20 +48,84 @@ impl DspManager {
         Self {
             sample_rate,
             collection: default(),
+            param_collection: default(),
+            cache: default(),
+            audio_clock: AudioClock::new(sample_rate),
         }
     }
 
-    pub(crate) fn add_graph<D: DspGraph>(&mut self, dsp_graph: D, source_type: SourceType) {
-        self.collection.insert(
-            dsp_graph.id(),
-            DspSource::new(dsp_graph, self.sample_rate, source_type),
+    /// Replace the render cache with one that keeps at most `limit` bytes.
+    pub(crate) fn with_render_cache_limit(mut self, limit: usize) -> Self {
+        self.cache = Arc::new(RenderCache::new(limit));
+        self
+    }
+
+    /// Register the DSP graph.
+    ///
+    /// If a DSP graph with the same ID is already registered,
+    /// the existing one is kept.
+    pub(crate) fn add_graph<D: DspGraph>(
+        &mut self,
+        dsp_graph: D,
+        source_type: SourceType,
+    ) -> Result<(), DspError> {
+        let id = dsp_graph.id();
+
+        self.check_duplicate(id)?;
+
+        let mut dsp_source = DspSource::new(dsp_graph, self.sample_rate, source_type);
+        dsp_source.cache = Some(self.cache.clone());
+        dsp_source.audio_clock = Some(self.audio_clock.clone());
+
+        self.collection.insert(id, dsp_source);
+
+        Ok(())
+    }
+
+    /// Register the parameterized DSP graph.
+    ///
+    /// See [`DspManager::add_graph`].
+    pub(crate) fn add_param_graph<P, D>(
+        &mut self,
+        dsp_graph: D,
+        source_type: SourceType,
+    ) -> Result<(), DspError>
+    where
+        P: Send + Sync + 'static,
+        D: ParamDspGraph<P>,
+    {
+        let id = dsp_graph.id();
+
+        self.check_duplicate(id)?;
+
+        let dsp_graph: Arc<dyn ParamDspGraph<P>> = Arc::new(dsp_graph);
+
+        self.param_collection.insert(
+            id,
+            ParamSource {
+                dsp_graph: Box::new(dsp_graph),
+                source_type,
+            },
         );
+
+        Ok(())
+    }
+
+    fn check_duplicate(&self, id: Uuid) -> Result<(), DspError> {
+        if self.collection.contains_key(&id) || self.param_collection.contains_key(&id) {
+            Err(DspError::DuplicateGraph(id))
+        } else {
+            Ok(())
+        }
     }
 
     /// Get the DSP source given a DSP graph.
+    ///
+    /// Each call returns a DSP source with its own
+    /// [`Controls`](crate::controls::Controls).
     #[allow(clippy::needless_pass_by_value)]
     pub fn get_graph<D: DspGraph>(&self, dsp_graph: D) -> Option<DspSource> {
-        self.collection.get(&dsp_graph.id()).cloned()
+        self.get_graph_by_id(&dsp_graph.id())
     }
 
     /// Get the DSP source given a UUID of the DSP graph.
@@ -55,6 +133,303 @@ impl DspManager {
     /// Useful if you cannot use the DSP graph directly.
     #[must_use]
     pub fn get_graph_by_id(&self, uuid: &Uuid) -> Option<DspSource> {
-        self.collection.get(uuid).cloned()
+        self.collection.get(uuid).map(DspSource::instance)
+    }
+
+    /// Get the DSP source given a DSP graph.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::GraphNotFound`] if the DSP graph is not registered.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn try_get_graph<D: DspGraph>(&self, dsp_graph: D) -> Result<DspSource, DspError> {
+        self.try_get_graph_by_id(&dsp_graph.id())
+    }
+
+    /// Get the DSP source given a UUID of the DSP graph.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::GraphNotFound`] if no DSP graph is registered with the given UUID.
+    pub fn try_get_graph_by_id(&self, uuid: &Uuid) -> Result<DspSource, DspError> {
+        self.get_graph_by_id(uuid)
+            .ok_or(DspError::GraphNotFound(*uuid))
+    }
+
+    /// Get the DSP source given a parameterized DSP graph
+    /// and the parameter to generate it with.
+    ///
+    /// Each call returns a DSP source that generates
+    /// a new DSP graph with the given parameter.
+    /// These are never cached, see [`DspSource`].
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn get_param_graph<P, D>(&self, dsp_graph: D, params: P) -> Option<DspSource>
+    where
+        P: Send + Sync + 'static,
+        D: ParamDspGraph<P>,
+    {
+        self.try_get_param_graph(dsp_graph, params).ok()
+    }
+
+    /// Get the DSP source given a UUID of the parameterized DSP graph
+    /// and the parameter to generate it with.
+    ///
+    /// See [`DspManager::get_param_graph`].
+    pub fn get_param_graph_by_id<P: Send + Sync + 'static>(
+        &self,
+        uuid: &Uuid,
+        params: P,
+    ) -> Option<DspSource> {
+        self.try_get_param_graph_by_id(uuid, params).ok()
+    }
+
+    /// Get the DSP source given a parameterized DSP graph
+    /// and the parameter to generate it with.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::GraphNotFound`] if the DSP graph is not registered.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn try_get_param_graph<P, D>(&self, dsp_graph: D, params: P) -> Result<DspSource, DspError>
+    where
+        P: Send + Sync + 'static,
+        D: ParamDspGraph<P>,
+    {
+        self.try_get_param_graph_by_id(&dsp_graph.id(), params)
+    }
+
+    /// Get the DSP source given a UUID of the parameterized DSP graph
+    /// and the parameter to generate it with.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::GraphNotFound`] if no parameterized DSP graph
+    /// is registered with the given UUID,
+    /// or [`DspError::ParamMismatch`] if it takes a different parameter type.
+    pub fn try_get_param_graph_by_id<P: Send + Sync + 'static>(
+        &self,
+        uuid: &Uuid,
+        params: P,
+    ) -> Result<DspSource, DspError> {
+        let param_source = self
+            .param_collection
+            .get(uuid)
+            .ok_or(DspError::GraphNotFound(*uuid))?;
+
+        let dsp_graph = param_source
+            .dsp_graph
+            .downcast_ref::<Arc<dyn ParamDspGraph<P>>>()
+            .ok_or(DspError::ParamMismatch(*uuid))?;
+
+        let mut dsp_source = DspSource::new(
+            WithParams {
+                dsp_graph: dsp_graph.clone(),
+                params,
+            },
+            self.sample_rate,
+            param_source.source_type,
+        );
+        dsp_source.audio_clock = Some(self.audio_clock.clone());
+
+        Ok(dsp_source)
+    }
+
+    /// The sample rate of the DSP sources from this manager.
+    #[must_use]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// The clock advanced by the DSP sources from this manager.
+    ///
+    /// This is also inserted as a resource by the [`DspPlugin`](crate::DspPlugin).
+    #[must_use]
+    pub fn audio_clock(&self) -> &AudioClock {
+        &self.audio_clock
+    }
+
+    /// Render the given DSP graph and save it as a WAV file in the given path.
+    ///
+    /// See [`DspSource::bounce`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::GraphNotFound`] if the DSP graph is not registered,
+    /// or [`DspError::Io`] if the file cannot be created or written.
+    pub fn bounce_to_file<D: DspGraph>(
+        &self,
+        dsp_graph: D,
+        path: impl AsRef<Path>,
+        duration: f32,
+        sample_rate: f32,
+    ) -> Result<(), DspError> {
+        self.try_get_graph(dsp_graph)?
+            .bounce_to_file(path, duration, sample_rate)
+    }
+
+    /// Remove the cached renders of the given DSP graph.
+    ///
+    /// Static DSP sources are only rendered once.
+    /// Use this if the DSP graph would now render differently,
+    /// for example after changing a [`Shared`](fundsp::hacker32::Shared) variable it reads.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn invalidate_graph<D: DspGraph>(&self, dsp_graph: D) {
+        self.invalidate_graph_by_id(&dsp_graph.id());
+    }
+
+    /// Remove the cached renders of the DSP graph with the given UUID.
+    ///
+    /// See [`DspManager::invalidate_graph`].
+    pub fn invalidate_graph_by_id(&self, uuid: &Uuid) {
+        self.cache.invalidate(uuid);
+    }
+
+    /// Remove the cached renders of every DSP graph.
+    ///
+    /// The cache is also bounded,
+    /// see [`DspPlugin::with_render_cache_limit`](crate::DspPlugin::with_render_cache_limit).
+    pub fn clear_render_cache(&self) {
+        self.cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports, clippy::float_cmp)]
+
+    use {
+        super::DspManager,
+        crate::{
+            backend::{Backend, DefaultBackend},
+            controls::Controls,
+            dsp_error::DspError,
+            dsp_graph::{controllable, DspGraph, ParamDspGraph},
+            dsp_source::SourceType,
+        },
+        fundsp::hacker32::*,
+    };
+
+    #[test]
+    fn duplicate_graph() {
+        let mut dsp_manager = DspManager::new(44100.0);
+
+        let sine = || sine_hz(440.0);
+        let square = || square_hz(440.0);
+
+        dsp_manager.add_graph(sine, SourceType::Dynamic).unwrap();
+        dsp_manager
+            .add_graph(square.named("sine"), SourceType::Dynamic)
+            .unwrap();
+
+        assert!(matches!(
+            dsp_manager.add_graph(sine, SourceType::Static { duration: 1.0 }),
+            Err(DspError::DuplicateGraph(id)) if id == sine.id()
+        ));
+        assert!(matches!(
+            dsp_manager.add_graph(sine.named("sine"), SourceType::Dynamic),
+            Err(DspError::DuplicateGraph(_))
+        ));
+
+        // The first registration is kept.
+        assert!(matches!(
+            dsp_manager.get_graph(sine).unwrap().source_type,
+            SourceType::Dynamic
+        ));
+    }
+
+    #[test]
+    fn param_graph() {
+        let mut dsp_manager = DspManager::new(44100.0);
+
+        let level = |level: &f32| constant(*level);
+
+        dsp_manager
+            .add_param_graph(level, SourceType::Dynamic)
+            .unwrap();
+
+        assert!(matches!(
+            dsp_manager.add_param_graph(level.named("level"), SourceType::Dynamic),
+            Ok(())
+        ));
+        assert!(matches!(
+            dsp_manager.add_param_graph(level, SourceType::Dynamic),
+            Err(DspError::DuplicateGraph(_))
+        ));
+
+        let mut quiet = dsp_manager
+            .get_param_graph(level, 0.25)
+            .unwrap()
+            .into_iter();
+        let mut loud = dsp_manager
+            .get_param_graph(level, 0.75)
+            .unwrap()
+            .into_iter();
+
+        assert_eq!(quiet.next(), Some([0.25, 0.25]));
+        assert_eq!(loud.next(), Some([0.75, 0.75]));
+
+        assert!(matches!(
+            dsp_manager.try_get_param_graph_by_id(&ParamDspGraph::<f32>::id(&level), 1_u32),
+            Err(DspError::ParamMismatch(_))
+        ));
+        assert!(dsp_manager
+            .get_graph_by_id(&ParamDspGraph::<f32>::id(&level))
+            .is_none());
+    }
+
+    #[test]
+    fn missing_graph() {
+        let dsp_manager = DspManager::new(44100.0);
+
+        let sine = || sine_hz(440.0);
+
+        assert!(matches!(
+            dsp_manager.try_get_graph(sine),
+            Err(DspError::GraphNotFound(id)) if id == sine.id()
+        ));
+    }
+
+    #[test]
+    fn dynamic_source_conversion() {
+        let mut dsp_manager = DspManager::new(44100.0);
+
+        let sine = || sine_hz(440.0);
+
+        dsp_manager.add_graph(sine, SourceType::Dynamic).unwrap();
+
+        let dsp_source = dsp_manager.try_get_graph(sine).unwrap();
+
+        assert!(matches!(
+            DefaultBackend::try_convert_to_audio_source(dsp_source),
+            Err(DspError::DynamicSource)
+        ));
+    }
+
+    #[test]
+    fn static_instances_with_different_controls() {
+        let mut dsp_manager = DspManager::new(100.0);
+
+        let level = controllable(|controls: &Controls| var(&controls.shared("level", 0.5)));
+
+        dsp_manager
+            .add_graph(level, SourceType::Static { duration: 0.1 })
+            .unwrap();
+
+        let quiet = dsp_manager.get_graph(level).unwrap();
+        let loud = dsp_manager.get_graph(level).unwrap();
+
+        quiet.controls().set("level", 0.25);
+        loud.controls().set("level", 0.75);
+
+        assert_eq!(quiet.to_frames().unwrap()[0], [0.25, 0.25]);
+        assert_eq!(loud.to_frames().unwrap()[0], [0.75, 0.75]);
+
+        let quiet_again = dsp_manager.get_graph(level).unwrap();
+        quiet_again.controls().set("level", 0.25);
+
+        assert!(std::sync::Arc::ptr_eq(
+            &quiet.to_frames().unwrap(),
+            &quiet_again.to_frames().unwrap()
+        ));
     }
 }
@@ -2,24 +2,69 @@
 //! a type that is analogous to `AudioSource` in `bevy_audio`.
 
 use {
-    crate::dsp_graph::DspGraph,
+    crate::{
+        audio_clock::{AudioClock, ClockWriter, SourceClock},
+        controls::Controls,
+        dsp_bus::DspBus,
+        dsp_emitter::{DspEmitter, SpatialGains},
+        dsp_error::DspError,
+        dsp_graph::DspGraph,
+        render_cache::{RenderCache, RenderKey},
+        renderer::Renderer,
+        resampler::Resampler,
+        static_renderer::RenderProgress,
+        wav::{apply_format, write_wav},
+    },
     bevy::reflect::{TypePath, TypeUuid},
-    fundsp::{hacker32::AudioUnit32, wave::Wave32},
-    std::{cell::RefCell, sync::Arc},
+    fundsp::{hacker32::Shared, MAX_BUFFER_SIZE},
+    std::{
+        cell::RefCell,
+        fs::File,
+        io::{BufWriter, Write},
+        path::Path,
+        sync::Arc,
+    },
 };
 
 /// A DSP source similar to `AudioSource` in `bevy_audio`.
 ///
 /// These can be played directly when the [`SourceType`] is dynamic,
 /// otherwise, the DSP source must be played with a given duration.
+///
+/// Static DSP sources from the [`DspManager`](crate::dsp_manager::DspManager)
+/// are only rendered once for each sample rate, duration,
+/// [`Channels`], [`SampleFormat`] and values of the [`Controls`].
+/// Later plays reuse the cached render.
+/// If the DSP graph renders differently over time,
+/// for example when it reads a [`Shared`] variable,
+/// use [`DspManager::invalidate_graph`](crate::dsp_manager::DspManager::invalidate_graph)
+/// to render it again.
+/// DSP sources of parameterized DSP graphs are never cached.
 #[derive(TypeUuid, Clone, TypePath)]
 #[uuid = "107a9069-d37d-46a8-92f2-23ec23b73bf6"]
 pub struct DspSource {
     pub(crate) dsp_graph: Arc<dyn DspGraph>,
     pub(crate) sample_rate: f32,
     pub(crate) source_type: SourceType,
+    pub(crate) channels: Channels,
+    pub(crate) resample_quality: ResampleQuality,
+    pub(crate) block_size: usize,
+    pub(crate) completion: Completion,
+    pub(crate) sample_format: SampleFormat,
+    pub(crate) cache: Option<Arc<RenderCache>>,
+    pub(crate) progress: Option<Arc<RenderProgress>>,
+    pub(crate) controls: Controls,
+    pub(crate) inputs: Vec<String>,
+    pub(crate) meter: Option<Shared<f32>>,
+    pub(crate) clock: SourceClock,
+    pub(crate) audio_clock: Option<AudioClock>,
+    pub(crate) spatial: Option<Arc<SpatialGains>>,
+    pub(crate) bus: DspBus,
 }
 
+/// The number of frames rendered between each progress report.
+const PROGRESS_INTERVAL: usize = 4096;
+
 /// The type of the [`DspSource`].
 #[derive(Debug, Clone, Copy)]
 pub enum SourceType {
@@ -28,7 +73,8 @@ pub enum SourceType {
     /// Therefore, the audio is of definite length,
     /// and the sound last for the given duration.
     ///
-    /// See [`Wave32`](fundsp::wave::Wave32) on how this is converted.
+    /// The bytes are rendered using the [`SampleFormat`]
+    /// and [`Channels`] of the DSP source.
     Static {
         /// The duration of the source in seconds.
         duration: f32,
@@ -39,9 +85,111 @@ pub enum SourceType {
     /// and not referenced from an internal collection of bytes.
     ///
     /// See [`Iter`].
+    ///
+    /// A dynamic source can still end itself, see [`Completion`].
     Dynamic,
 }
 
+/// Conditions under which a dynamic [`DspSource`] ends.
+///
+/// By default, dynamic DSP sources never end.
+/// If multiple conditions are given,
+/// the source ends as soon as any of them is met.
+///
+/// Once ended, each backend reports the playing sound as finished,
+/// so the voice can be freed.
+///
+/// ```no_run
+/// # use bevy_fundsp::prelude::*;
+/// // Stop after 10 seconds,
+/// // or after 200 milliseconds of silence.
+/// let completion = Completion::new()
+///     .with_max_duration(10.0)
+///     .with_silence(0.0001, 0.2);
+/// ```
+#[derive(Clone, Default)]
+pub struct Completion {
+    pub(crate) max_duration: Option<f32>,
+    pub(crate) silence: Option<(f32, f32)>,
+    pub(crate) flag: Option<Shared<f32>>,
+}
+
+impl Completion {
+    /// Create a completion that never ends.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// End the source after the given duration in seconds.
+    #[must_use]
+    pub fn with_max_duration(mut self, duration: f32) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// End the source when both channels stay at or below the given amplitude
+    /// for the given duration in seconds.
+    #[must_use]
+    pub fn with_silence(mut self, threshold: f32, hold: f32) -> Self {
+        self.silence = Some((threshold.abs(), hold));
+        self
+    }
+
+    /// End the source when the given shared variable becomes nonzero.
+    ///
+    /// The variable is checked at the start of each rendered block.
+    #[must_use]
+    pub fn with_flag(mut self, flag: &Shared<f32>) -> Self {
+        self.flag = Some(flag.clone());
+        self
+    }
+}
+
+/// The number of channels a [`DspSource`] is played with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Channels {
+    /// A single channel.
+    ///
+    /// Stereo graphs are downmixed by averaging both channels.
+    Mono,
+    /// Two channels, left and right.
+    #[default]
+    Stereo,
+}
+
+/// The format of each sample when rendering a static [`DspSource`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SampleFormat {
+    /// 16-bit signed integer.
+    #[default]
+    Int16,
+    /// 24-bit signed integer.
+    Int24,
+    /// 32-bit signed integer.
+    Int32,
+    /// 32-bit float.
+    ///
+    /// This keeps the rendered samples as is.
+    Float32,
+}
+
+/// The interpolation used when the sample rate requested by the backend
+/// differs from the sample rate of the [`DspSource`].
+///
+/// This is used by `bevy_kira_audio` and `bevy_oddio`,
+/// as they request frames at the sample rate of the audio device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Use the nearest frame. This is the cheapest, but aliases the most.
+    Nearest,
+    /// Linearly interpolate between two frames.
+    Linear,
+    /// Interpolate between four frames using a cubic spline.
+    #[default]
+    Cubic,
+}
+
 impl DspSource {
     pub(crate) fn new<D: DspGraph>(
         dsp_graph: D,
@@ -52,37 +200,366 @@ impl DspSource {
             dsp_graph: Arc::new(dsp_graph),
             sample_rate,
             source_type,
+            channels: Channels::default(),
+            resample_quality: ResampleQuality::default(),
+            block_size: MAX_BUFFER_SIZE,
+            completion: Completion::default(),
+            sample_format: SampleFormat::default(),
+            cache: None,
+            progress: None,
+            controls: Controls::new(),
+            inputs: Vec::new(),
+            meter: None,
+            clock: SourceClock::new(sample_rate),
+            audio_clock: None,
+            spatial: None,
+            bus: DspBus::MASTER,
+        }
+    }
+
+    /// Create a new instance of this DSP source
+    /// with its own [`Controls`] and [`SourceClock`].
+    pub(crate) fn instance(&self) -> Self {
+        Self {
+            controls: Controls::new(),
+            clock: SourceClock::new(self.sample_rate),
+            ..self.clone()
+        }
+    }
+
+    /// The named parameters of this DSP source.
+    ///
+    /// These are shared with clones of this DSP source,
+    /// but not with other DSP sources from the
+    /// [`DspManager`](crate::dsp_manager::DspManager).
+    /// See [`Controls`].
+    #[must_use]
+    pub fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    /// The number of frames this DSP source has played.
+    ///
+    /// Like the [`Controls`], this is shared with clones of this DSP source.
+    /// See [`SourceClock`].
+    #[must_use]
+    pub fn clock(&self) -> &SourceClock {
+        &self.clock
+    }
+
+    /// Set the number of channels this DSP source is played with.
+    ///
+    /// By default, DSP sources are played in stereo.
+    ///
+    /// Static sources are rendered with the given channels in every backend.
+    /// Dynamic sources only respect this in `bevy_audio`.
+    #[must_use]
+    pub fn with_channels(mut self, channels: Channels) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// The number of channels this DSP source is played with.
+    #[must_use]
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    /// Set the format of each sample when rendering this static DSP source.
+    ///
+    /// By default, this is [`SampleFormat::Int16`].
+    #[must_use]
+    pub fn with_sample_format(mut self, sample_format: SampleFormat) -> Self {
+        self.sample_format = sample_format;
+        self
+    }
+
+    /// The format of each sample when rendering this static DSP source.
+    #[must_use]
+    pub fn sample_format(&self) -> SampleFormat {
+        self.sample_format
+    }
+
+    /// Set the interpolation used when the backend requests
+    /// a different sample rate than the sample rate of this DSP source.
+    #[must_use]
+    pub fn with_resample_quality(mut self, resample_quality: ResampleQuality) -> Self {
+        self.resample_quality = resample_quality;
+        self
+    }
+
+    /// The interpolation used when resampling this DSP source.
+    #[must_use]
+    pub fn resample_quality(&self) -> ResampleQuality {
+        self.resample_quality
+    }
+
+    /// Set the number of frames rendered at once when playing this DSP source.
+    ///
+    /// Dynamic sources are rendered in blocks,
+    /// so changes to [`Shared`](fundsp::hacker32::Shared) variables
+    /// only take effect at the start of the next block.
+    /// Smaller blocks respond faster, but are more expensive to render.
+    ///
+    /// The block size is clamped between 1 and [`MAX_BUFFER_SIZE`].
+    /// By default, this is [`MAX_BUFFER_SIZE`].
+    #[must_use]
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size.clamp(1, MAX_BUFFER_SIZE);
+        self
+    }
+
+    /// The number of frames rendered at once when playing this DSP source.
+    #[must_use]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Set the conditions under which this DSP source ends.
+    ///
+    /// This is only useful for dynamic sources,
+    /// as static sources already end after their duration.
+    #[must_use]
+    pub fn with_completion(mut self, completion: Completion) -> Self {
+        self.completion = completion;
+        self
+    }
+
+    /// Feed the inputs of the DSP graph from the given parameters
+    /// of its [`Controls`].
+    ///
+    /// The first input reads the first parameter, and so on.
+    /// The inputs are read at the start of each rendered block,
+    /// see [`DspSource::with_block_size`].
+    ///
+    /// ```no_run
+    /// # use bevy_fundsp::prelude::*;
+    /// # fn synth(dsp_source: DspSource) {
+    /// // A DSP graph with two inputs, such as `saw() * adsr_live(0.01, 0.1, 0.5, 0.2)`.
+    /// let dsp_source = dsp_source.with_inputs(&["frequency", "gate"]);
+    /// dsp_source.controls().set("frequency", 440.0);
+    /// dsp_source.controls().set("gate", 1.0);
+    /// # }
+    /// ```
+    ///
+    /// Playing a DSP graph that has more inputs than the given parameters,
+    /// or more than 16 inputs, panics.
+    #[must_use]
+    pub fn with_inputs(mut self, names: &[&str]) -> Self {
+        self.inputs = names.iter().map(|&name| name.to_owned()).collect();
+        self
+    }
+
+    /// Position this DSP source at the given [`DspEmitter`].
+    ///
+    /// Dynamic DSP sources are panned and attenuated
+    /// relative to the entity with the [`DspListener`](crate::dsp_emitter::DspListener).
+    /// Mono DSP graphs are panned,
+    /// while stereo DSP graphs have the gain of each channel adjusted.
+    /// DSP sources played with [`Channels::Mono`] have no channels to pan,
+    /// so they are only attenuated.
+    ///
+    /// Static DSP sources are rendered ahead of time,
+    /// so they are played without panning or attenuation.
+    #[must_use]
+    pub fn with_emitter(mut self, emitter: &DspEmitter) -> Self {
+        self.spatial = Some(emitter.gains());
+        self
+    }
+
+    /// Play this DSP source through the given bus.
+    ///
+    /// By default, DSP sources are played through the [`DspBus::MASTER`] bus.
+    /// See [`DspBuses`](crate::dsp_bus::DspBuses).
+    #[must_use]
+    pub fn with_bus(mut self, bus: DspBus) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    /// The bus this DSP source is played through.
+    #[must_use]
+    pub fn bus(&self) -> &DspBus {
+        &self.bus
+    }
+
+    /// Create a renderer for a new DSP graph of this DSP source.
+    fn renderer(&self, sample_rate: f32, block_size: usize, completion: &Completion) -> Renderer {
+        let mut audio_unit = self.dsp_graph.generate_graph_with_controls(&self.controls);
+        audio_unit.set_sample_rate(f64::from(sample_rate));
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|name| self.controls.shared(name, 0.0))
+            .collect();
+
+        Renderer::new(audio_unit, block_size, completion, sample_rate, inputs)
+            .with_meter(self.meter.clone())
+            .with_events(self.controls.subscribe())
+    }
+
+    /// Render the static DSP source into stereo frames.
+    ///
+    /// The source type must be static,
+    /// otherwise it returns [`DspError::DynamicSource`],
+    /// as it does not know how long it is.
+    pub(crate) fn render_static(&self) -> Result<Vec<[f32; 2]>, DspError> {
+        match self.source_type {
+            SourceType::Static { duration } => Ok(self.render(self.sample_rate, duration)),
+            SourceType::Dynamic => Err(DspError::DynamicSource),
         }
     }
 
+    /// Render the DSP graph for the given duration in seconds.
+    ///
+    /// This ignores the [`SourceType`] and [`Completion`] of the DSP source.
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    pub(crate) fn render(&self, sample_rate: f32, duration: f32) -> Vec<[f32; 2]> {
+        let number_of_frames = (f64::from(sample_rate) * f64::from(duration)).round() as usize;
+
+        let mut renderer = self.renderer(sample_rate, MAX_BUFFER_SIZE, &Completion::default());
+
+        let mut frames = Vec::with_capacity(number_of_frames);
+
+        while frames.len() < number_of_frames {
+            let chunk = (number_of_frames - frames.len()).min(PROGRESS_INTERVAL);
+            frames.extend((0..chunk).map(|_| renderer.next_frame().unwrap_or_default()));
+
+            if let Some(progress) = &self.progress {
+                #[allow(clippy::cast_precision_loss)]
+                progress.set(frames.len() as f32 / number_of_frames as f32);
+            }
+        }
+
+        frames
+    }
+
     /// Convert the DSP source to its corresponding bytes.
     ///
+    /// The bytes are a WAV file, using the [`SampleFormat`]
+    /// and [`Channels`] of the DSP source.
+    ///
     /// The source type must be static,
-    /// otherwise it will panic,
+    /// otherwise it returns [`DspError::DynamicSource`],
     /// as it does not know how long it is.
+    #[cfg_attr(not(any(feature = "bevy_audio", feature = "kira")), allow(dead_code))]
+    pub(crate) fn to_bytes(&self) -> Result<Arc<[u8]>, DspError> {
+        let render = || -> Result<Arc<[u8]>, DspError> {
+            let frames = self.render_static()?;
+
+            let mut buffer = Vec::new();
+
+            self.write_frames(&mut buffer, &frames, self.sample_rate)?;
+
+            Ok(buffer.into())
+        };
+
+        match (&self.cache, self.render_key()) {
+            (Some(cache), Some(key)) => cache.bytes(key, render),
+            _ => render(),
+        }
+    }
+
+    /// Render the static DSP source into stereo frames,
+    /// with the [`SampleFormat`] and [`Channels`] of the DSP source applied.
     ///
-    /// Internally, this uses [`fundsp::wave::Wave32`].
-    #[cfg_attr(feature = "oddio", allow(dead_code))]
-    pub(crate) fn to_bytes(&self) -> Vec<u8> {
-        let duration = match self.source_type {
-            SourceType::Static { duration } => duration,
-            SourceType::Dynamic => panic!("Only static DSP sources can be converted into bytes."),
+    /// This is used by backends that play frames directly.
+    pub(crate) fn to_frames(&self) -> Result<Arc<[[f32; 2]]>, DspError> {
+        let render = || -> Result<Arc<[[f32; 2]]>, DspError> {
+            let mut frames = self.render_static()?;
+
+            apply_format(&mut frames, self.channels, self.sample_format);
+
+            Ok(frames.into())
         };
 
-        let mut node = self.dsp_graph.generate_graph();
+        match (&self.cache, self.render_key()) {
+            (Some(cache), Some(key)) => cache.frames(key, render),
+            _ => render(),
+        }
+    }
 
-        let wave = Wave32::render(
-            f64::from(self.sample_rate),
-            f64::from(duration),
-            node.as_mut(),
-        );
+    fn render_key(&self) -> Option<RenderKey> {
+        match self.source_type {
+            SourceType::Static { duration } => Some(RenderKey {
+                id: self.dsp_graph.id(),
+                sample_rate: self.sample_rate.to_bits(),
+                duration: duration.to_bits(),
+                channels: self.channels,
+                sample_format: self.sample_format,
+                controls: self.controls.values_hash(),
+            }),
+            SourceType::Dynamic => None,
+        }
+    }
 
-        let mut buffer = Vec::new();
+    /// Render the DSP source and write it as a WAV file.
+    ///
+    /// This does not need a backend nor an audio device,
+    /// and works with both static and dynamic DSP sources.
+    /// The WAV file uses the [`SampleFormat`] and [`Channels`] of the DSP source.
+    ///
+    /// ```no_run
+    /// # use bevy_fundsp::prelude::*;
+    /// # fn bounce(dsp_source: DspSource) -> Result<(), DspError> {
+    /// let mut buffer = Vec::new();
+    /// dsp_source.bounce(&mut buffer, 5.0, 48000.0)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::Io`] if the writer fails.
+    pub fn bounce(
+        &self,
+        mut writer: impl Write,
+        duration: f32,
+        sample_rate: f32,
+    ) -> Result<(), DspError> {
+        let frames = self.render(sample_rate, duration);
 
-        wave.write_wav16(&mut buffer)
-            .unwrap_or_else(|err| panic!("Cannot write wave to buffer. Error: {err:?}"));
+        self.write_frames(&mut writer, &frames, sample_rate)?;
+        writer.flush()?;
 
-        buffer
+        Ok(())
+    }
+
+    /// Render the DSP source and save it as a WAV file in the given path.
+    ///
+    /// See [`DspSource::bounce`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::Io`] if the file cannot be created or written.
+    pub fn bounce_to_file(
+        &self,
+        path: impl AsRef<Path>,
+        duration: f32,
+        sample_rate: f32,
+    ) -> Result<(), DspError> {
+        let file = File::create(path)?;
+
+        self.bounce(BufWriter::new(file), duration, sample_rate)
+    }
+
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn write_frames(
+        &self,
+        writer: &mut dyn Write,
+        frames: &[[f32; 2]],
+        sample_rate: f32,
+    ) -> Result<(), DspError> {
+        write_wav(
+            writer,
+            frames,
+            sample_rate as u32,
+            self.channels,
+            self.sample_format,
+        )?;
+
+        Ok(())
     }
 }
 
@@ -93,7 +570,15 @@ impl IntoIterator for DspSource {
     fn into_iter(self) -> Self::IntoIter {
         Iter {
             sample_rate: self.sample_rate,
-            audio_unit: RefCell::new(self.dsp_graph.generate_graph()),
+            renderer: RefCell::new(
+                self.renderer(self.sample_rate, self.block_size, &self.completion)
+                    .with_clock(ClockWriter::new(
+                        self.clock.clone(),
+                        self.audio_clock.clone(),
+                    ))
+                    .with_spatial(self.spatial.clone(), self.channels == Channels::Mono),
+            ),
+            resampler: RefCell::new(Resampler::new(self.resample_quality)),
         }
     }
 }
@@ -101,43 +586,73 @@ impl IntoIterator for DspSource {
 /// An iterator of the DSP source
 /// whose item is a stereo sample.
 ///
-/// This is infinite, and would never return `None`.
+/// This is infinite, and would never return `None`,
+/// unless a [`Completion`] is given.
+///
+/// Internally, the DSP graph is rendered in blocks.
+/// See [`DspSource::with_block_size`].
 pub struct Iter {
+    #[cfg_attr(
+        not(any(feature = "bevy_audio", feature = "kira", feature = "oddio")),
+        allow(dead_code)
+    )]
     pub(crate) sample_rate: f32,
-    pub(crate) audio_unit: RefCell<Box<dyn AudioUnit32>>,
+    pub(crate) renderer: RefCell<Renderer>,
+    #[cfg_attr(not(any(feature = "kira", feature = "oddio")), allow(dead_code))]
+    pub(crate) resampler: RefCell<Resampler>,
 }
 
 pub(crate) trait Source {
     type Frame;
 
-    fn sample_rate(&self) -> f32;
     fn sample(&self) -> Self::Frame;
 
-    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-    fn advance(&self, dt: f32) {
-        for _ in 0..(self.sample_rate() * dt) as usize {
-            self.sample();
-        }
-    }
+    /// Get the next frame for an output
+    /// whose frames are `dt` seconds apart.
+    ///
+    /// The frames are resampled from the sample rate of the source.
+    #[cfg_attr(not(any(feature = "kira", feature = "oddio")), allow(dead_code))]
+    fn resample(&self, dt: f64) -> Self::Frame;
 }
 
 impl Iter {
+    /// Whether the DSP source has ended.
+    ///
+    /// See [`Completion`].
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.renderer.borrow().is_finished()
+    }
+
     /// Convert the iterator into a different iterator
     /// that returns mono samples.
     pub fn into_mono(self) -> IterMono {
         IterMono(self)
     }
+
+    /// Convert the iterator into a different iterator
+    /// that returns interleaved stereo samples.
+    pub fn into_interleaved(self) -> IterInterleaved {
+        IterInterleaved {
+            iter: self,
+            right: None,
+        }
+    }
 }
 
 impl Source for Iter {
     type Frame = [f32; 2];
 
-    fn sample_rate(&self) -> f32 {
-        self.sample_rate
+    fn sample(&self) -> Self::Frame {
+        self.renderer.borrow_mut().next_frame().unwrap_or_default()
     }
 
-    fn sample(&self) -> Self::Frame {
-        self.audio_unit.borrow_mut().get_stereo().into()
+    fn resample(&self, dt: f64) -> Self::Frame {
+        let step = f64::from(self.sample_rate) * dt;
+
+        self.resampler
+            .borrow_mut()
+            .next_frame(step, || self.sample())
     }
 }
 
@@ -145,7 +660,7 @@ impl Iterator for Iter {
     type Item = [f32; 2];
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some(self.sample())
+        self.renderer.get_mut().next_frame()
     }
 }
 
@@ -158,12 +673,14 @@ pub struct IterMono(pub(crate) Iter);
 impl Source for IterMono {
     type Frame = f32;
 
-    fn sample_rate(&self) -> f32 {
-        self.0.sample_rate
+    fn sample(&self) -> f32 {
+        let [left, right] = self.0.sample();
+        f32::midpoint(left, right)
     }
 
-    fn sample(&self) -> f32 {
-        self.0.audio_unit.borrow_mut().get_mono()
+    fn resample(&self, dt: f64) -> f32 {
+        let [left, right] = self.0.resample(dt);
+        f32::midpoint(left, right)
     }
 }
 
@@ -171,7 +688,32 @@ impl Iterator for IterMono {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some(self.sample())
+        let [left, right] = self.0.next()?;
+        Some(f32::midpoint(left, right))
+    }
+}
+
+/// An iterator that returns interleaved stereo samples.
+/// This is similar to [`Iter`],
+/// but the left and right samples of each frame are returned one after the other.
+///
+/// Internally, only `bevy_audio` uses this.
+pub struct IterInterleaved {
+    pub(crate) iter: Iter,
+    pub(crate) right: Option<f32>,
+}
+
+impl Iterator for IterInterleaved {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(right) = self.right.take() {
+            return Some(right);
+        }
+
+        let [left, right] = self.iter.next()?;
+        self.right = Some(right);
+        Some(left)
     }
 }
 
@@ -180,9 +722,10 @@ mod tests {
     #![allow(clippy::wildcard_imports)]
 
     use {
-        super::{DspSource, SourceType},
-        crate::DEFAULT_SAMPLE_RATE,
+        super::{Channels, Completion, DspSource, SourceType},
+        crate::{render_cache::RenderCache, static_renderer::RenderProgress, DEFAULT_SAMPLE_RATE},
         fundsp::hacker32::*,
+        std::sync::Arc,
     };
 
     #[test]
@@ -206,6 +749,22 @@ mod tests {
         assert_eq!(iter.next(), Some(440.0));
     }
 
+    #[test]
+    fn interleaved_signal() {
+        let source = DspSource::new(
+            || constant((440.0, 220.0)),
+            *DEFAULT_SAMPLE_RATE,
+            SourceType::Dynamic,
+        );
+
+        let mut iter = source.into_iter().into_interleaved();
+
+        assert_eq!(iter.next(), Some(440.0));
+        assert_eq!(iter.next(), Some(220.0));
+        assert_eq!(iter.next(), Some(440.0));
+        assert_eq!(iter.next(), Some(220.0));
+    }
+
     #[test]
     fn sine_wave_signal() {
         let sine_wave = || constant(440.0) >> sine();
@@ -215,9 +774,12 @@ mod tests {
         let iter = source.into_iter().into_mono();
         let mut signal = sine_wave();
 
+        // FunDSP wraps the phase of oscillators once per block in `process`,
+        // but on every sample in `tick`, so the phases are rounded differently
+        // and slowly drift apart. Over 1000 frames, this stays below 1e-4.
         for sample in iter.take(1_000) {
             let signal_sample = signal.get_mono();
-            assert!((signal_sample - sample).abs() < f32::EPSILON);
+            assert!((signal_sample - sample).abs() < 1e-4);
         }
     }
 
@@ -228,7 +790,8 @@ mod tests {
 
         let sine_wave = move || var(&sine_wave_frequency);
 
-        let source = DspSource::new(sine_wave, *DEFAULT_SAMPLE_RATE, SourceType::Dynamic);
+        let source =
+            DspSource::new(sine_wave, *DEFAULT_SAMPLE_RATE, SourceType::Dynamic).with_block_size(1);
 
         let mut iter = source.into_iter();
 
@@ -254,4 +817,126 @@ mod tests {
         assert_eq!(iter.next(), Some(440.0));
         assert_eq!(iter.next(), Some(440.0));
     }
+
+    #[test]
+    fn controllable_at_block_boundary() {
+        let frequency = shared(440.0);
+        let sine_wave_frequency = frequency.clone();
+
+        let sine_wave = move || var(&sine_wave_frequency);
+
+        let source =
+            DspSource::new(sine_wave, *DEFAULT_SAMPLE_RATE, SourceType::Dynamic).with_block_size(4);
+
+        let mut iter = source.into_iter();
+
+        assert_eq!(iter.next(), Some([440.0, 440.0]));
+
+        frequency.set_value(880.0);
+
+        assert_eq!(iter.next(), Some([440.0, 440.0]));
+        assert_eq!(iter.next(), Some([440.0, 440.0]));
+        assert_eq!(iter.next(), Some([440.0, 440.0]));
+        assert_eq!(iter.next(), Some([880.0, 880.0]));
+    }
+
+    #[test]
+    fn completion() {
+        let source = DspSource::new(|| constant(1.0), 1000.0, SourceType::Dynamic)
+            .with_completion(Completion::new().with_max_duration(0.1));
+
+        assert_eq!(source.into_iter().count(), 100);
+
+        let source = DspSource::new(
+            || envelope(|t| if t < 0.05 { 1.0 } else { 0.0 }),
+            1000.0,
+            SourceType::Dynamic,
+        )
+        .with_block_size(1)
+        .with_completion(Completion::new().with_silence(0.0, 0.02));
+
+        assert!((65..=75).contains(&source.into_iter().count()));
+
+        let done = shared(0.0);
+        let source = DspSource::new(|| constant(1.0), 1000.0, SourceType::Dynamic)
+            .with_block_size(1)
+            .with_completion(Completion::new().with_flag(&done));
+
+        let mut iter = source.into_iter();
+
+        assert_eq!(iter.next(), Some([1.0, 1.0]));
+        assert!(!iter.is_finished());
+
+        done.set_value(1.0);
+
+        assert_eq!(iter.next(), None);
+        assert!(iter.is_finished());
+    }
+
+    #[test]
+    fn bounce_dynamic_source() {
+        let source = DspSource::new(|| constant(0.5), *DEFAULT_SAMPLE_RATE, SourceType::Dynamic)
+            .with_channels(Channels::Mono);
+
+        let mut buffer = Vec::new();
+        source.bounce(&mut buffer, 0.5, 1000.0).unwrap();
+
+        // 44 bytes of header, and 500 frames of 16-bit mono samples.
+        assert_eq!(buffer.len(), 44 + 500 * 2);
+        assert_eq!(buffer[44..46], 16384_i16.to_le_bytes());
+    }
+
+    #[test]
+    fn cached_render() {
+        let cache = Arc::new(RenderCache::default());
+        let source = DspSource::new(
+            || constant(0.5),
+            *DEFAULT_SAMPLE_RATE,
+            SourceType::Static { duration: 0.1 },
+        );
+        let source = DspSource {
+            cache: Some(cache.clone()),
+            ..source
+        };
+
+        let first = source.to_bytes().unwrap();
+        let second = source.to_bytes().unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let mono = source
+            .clone()
+            .with_channels(Channels::Mono)
+            .to_bytes()
+            .unwrap();
+
+        assert!(!Arc::ptr_eq(&first, &mono));
+
+        cache.invalidate(&source.dsp_graph.id());
+
+        let third = source.to_bytes().unwrap();
+
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!(first, third);
+    }
+
+    #[test]
+    fn render_progress() {
+        let progress = Arc::new(RenderProgress::default());
+        let source = DspSource::new(
+            || constant(0.5),
+            *DEFAULT_SAMPLE_RATE,
+            SourceType::Static { duration: 0.5 },
+        );
+        let source = DspSource {
+            progress: Some(progress.clone()),
+            ..source
+        };
+
+        assert!(progress.get().abs() < f32::EPSILON);
+
+        source.to_bytes().unwrap();
+
+        assert!((progress.get() - 1.0).abs() < f32::EPSILON);
+    }
 }
@@ -31,17 +31,43 @@
 
 use {
     backend::{Backend, DefaultBackend},
-    bevy::prelude::{AddAsset, App, Plugin},
-    dsp_graph::DspGraph,
+    bevy::{
+        prelude::{AddAsset, App, IntoSystemConfigs, Plugin, PostUpdate, Update},
+        transform::TransformSystem,
+    },
+    dsp_bus::DspBuses,
+    dsp_emitter::update_dsp_emitters,
+    dsp_error::DspError,
+    dsp_graph::{DspGraph, ParamDspGraph},
     dsp_manager::DspManager,
     dsp_source::{DspSource, SourceType},
-    once_cell::sync::Lazy,
+    render_cache::DEFAULT_RENDER_CACHE_LIMIT,
+    static_renderer::{finish_static_renders, StaticRenderFinished, StaticRenderer},
+    std::{
+        fmt::{Display, Formatter},
+        marker::PhantomData,
+        sync::LazyLock,
+    },
 };
 
+pub mod audio_clock;
 pub mod backend;
+pub mod controls;
+pub mod dsp_bus;
+pub mod dsp_emitter;
+pub mod dsp_error;
 pub mod dsp_graph;
 pub mod dsp_manager;
 pub mod dsp_source;
+#[cfg(feature = "midi")]
+pub mod midi;
+pub mod mixer;
+mod render_cache;
+mod renderer;
+mod resampler;
+pub mod static_renderer;
+pub mod voice_allocator;
+mod wav;
 
 /// Add support for using [FunDSP graphs] in Bevy code.
 ///
@@ -53,8 +79,13 @@ pub mod dsp_source;
 ///     .add_plugin(DspPlugin::default())
 ///     .run()
 /// ```
-pub struct DspPlugin {
+///
+/// The DSP sources are played with the backend `B`,
+/// which is the [`DefaultBackend`] unless set by [`DspPlugin::with_backend`].
+pub struct DspPlugin<B: Backend = DefaultBackend> {
     sample_rate: f32,
+    render_cache_limit: usize,
+    _backend: PhantomData<fn() -> B>,
 }
 
 impl DspPlugin {
@@ -76,22 +107,134 @@ impl DspPlugin {
     /// ```
     #[allow(clippy::must_use_candidate)]
     pub fn new(sample_rate: f32) -> Self {
-        Self { sample_rate }
+        Self {
+            sample_rate,
+            render_cache_limit: DEFAULT_RENDER_CACHE_LIMIT,
+            _backend: PhantomData,
+        }
+    }
+
+    /// Construct the plugin using the sample rate
+    /// of the default output device.
+    ///
+    /// This returns an error if the device cannot be probed,
+    /// which usually happens on headless machines
+    /// such as CI runners and dedicated servers.
+    ///
+    /// ```no_run
+    /// # use bevy::prelude::*;
+    /// # use bevy_fundsp::prelude::*;
+    /// let dsp_plugin = DspPlugin::try_from_device().unwrap_or_else(|err| {
+    ///     warn!("{err}");
+    ///     DspPlugin::new(48000.0)
+    /// });
+    ///
+    /// App::new()
+    ///     .add_plugins(DefaultPlugins)
+    ///     .add_plugins(dsp_plugin)
+    ///     .run()
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DeviceError`] if there is no default output device,
+    /// or if its default stream config cannot be found.
+    pub fn try_from_device() -> Result<Self, DeviceError> {
+        device_sample_rate().map(Self::new)
+    }
+
+    /// Construct the plugin using the sample rate
+    /// of the default output device.
+    ///
+    /// If the device cannot be probed,
+    /// this logs a warning and uses the given sample rate instead.
+    #[must_use]
+    pub fn from_device_or(fallback_sample_rate: f32) -> Self {
+        Self::new(sample_rate_or(fallback_sample_rate))
+    }
+}
+
+impl<B: Backend> DspPlugin<B> {
+    /// Play the DSP sources with the given backend instead.
+    ///
+    /// Multiple backend features can be enabled at once,
+    /// so the backend can be chosen when building the app.
+    ///
+    /// ```no_run
+    /// # use bevy::prelude::*;
+    /// # use bevy_fundsp::prelude::*;
+    /// # #[cfg(feature = "kira")]
+    /// # fn main() {
+    /// use bevy_fundsp::backend::kira::KiraBackend;
+    ///
+    /// App::new()
+    ///     .add_plugins(DefaultPlugins)
+    ///     .add_plugins(bevy_kira_audio::AudioPlugin)
+    ///     .add_plugins(DspPlugin::default().with_backend::<KiraBackend>())
+    ///     .run()
+    /// # }
+    /// # #[cfg(not(feature = "kira"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// The [`StaticRenderer`] and [`StaticRenderFinished`] resources
+    /// are then registered for the given backend,
+    /// such as `StaticRenderer<KiraBackend>`.
+    #[must_use]
+    pub fn with_backend<C: Backend>(self) -> DspPlugin<C> {
+        DspPlugin {
+            sample_rate: self.sample_rate,
+            render_cache_limit: self.render_cache_limit,
+            _backend: PhantomData,
+        }
+    }
+
+    /// Set how many bytes of static renders are cached, 256 MiB by default.
+    ///
+    /// When the limit is reached,
+    /// the renders of the least recently played DSP sources are removed,
+    /// and rendered again the next time they are played.
+    #[must_use]
+    pub fn with_render_cache_limit(mut self, bytes: usize) -> Self {
+        self.render_cache_limit = bytes;
+        self
     }
 }
 
 impl Default for DspPlugin {
+    /// Construct the plugin using the sample rate
+    /// of the default output device.
+    ///
+    /// If the device cannot be probed,
+    /// this falls back to [`FALLBACK_SAMPLE_RATE`].
     fn default() -> Self {
         Self::new(*DEFAULT_SAMPLE_RATE)
     }
 }
 
-impl Plugin for DspPlugin {
+impl<B: Backend> Plugin for DspPlugin<B> {
     fn build(&self, app: &mut App) {
-        app.insert_resource(DspManager::new(self.sample_rate))
-            .add_asset::<DspSource>();
+        let dsp_manager =
+            DspManager::new(self.sample_rate).with_render_cache_limit(self.render_cache_limit);
 
-        DefaultBackend::init_app(app);
+        app.insert_resource(dsp_manager.audio_clock().clone())
+            .insert_resource(dsp_manager)
+            .init_resource::<DspBuses>()
+            .add_asset::<DspSource>()
+            .init_resource::<StaticRenderer<B>>()
+            .add_event::<StaticRenderFinished<B>>()
+            .add_systems(Update, finish_static_renders::<B>)
+            .add_systems(
+                PostUpdate,
+                update_dsp_emitters.after(TransformSystem::TransformPropagate),
+            );
+
+        #[cfg(feature = "midi")]
+        app.add_asset::<midi::MidiFile>()
+            .init_asset_loader::<midi::MidiFileLoader>()
+            .add_systems(Update, midi::advance_midi_players);
+
+        B::init_app(app);
     }
 }
 
@@ -114,43 +257,178 @@ pub trait DspAppExt {
     ///     sine_hz(440.0)
     /// }
     /// ```
+    ///
+    /// Each DSP graph must have a unique ID.
+    /// If a DSP graph with the same ID is already registered,
+    /// this logs an error and keeps the existing one.
     fn add_dsp_source<D: DspGraph>(&mut self, dsp_graph: D, source_type: SourceType) -> &mut Self;
+
+    /// Register a DSP source with the given [`SourceType`].
+    ///
+    /// See [`DspAppExt::add_dsp_source`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::DuplicateGraph`] if a DSP graph with the same ID is already registered.
+    fn try_add_dsp_source<D: DspGraph>(
+        &mut self,
+        dsp_graph: D,
+        source_type: SourceType,
+    ) -> Result<&mut Self, DspError>;
+
+    /// Register a parameterized DSP source with the given [`SourceType`].
+    ///
+    /// The type to be registered must implement [`ParamDspGraph`].
+    /// Like [`DspAppExt::add_dsp_source`],
+    /// this logs an error if a DSP graph with the same ID is already registered.
+    fn add_param_dsp_source<P, D>(&mut self, dsp_graph: D, source_type: SourceType) -> &mut Self
+    where
+        P: Send + Sync + 'static,
+        D: ParamDspGraph<P>;
+
+    /// Register a parameterized DSP source with the given [`SourceType`].
+    ///
+    /// See [`DspAppExt::add_param_dsp_source`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::DuplicateGraph`] if a DSP graph with the same ID is already registered.
+    fn try_add_param_dsp_source<P, D>(
+        &mut self,
+        dsp_graph: D,
+        source_type: SourceType,
+    ) -> Result<&mut Self, DspError>
+    where
+        P: Send + Sync + 'static,
+        D: ParamDspGraph<P>;
 }
 
 impl DspAppExt for App {
     fn add_dsp_source<D: DspGraph>(&mut self, dsp_graph: D, source_type: SourceType) -> &mut Self {
+        if let Err(err) = self.try_add_dsp_source(dsp_graph, source_type) {
+            bevy::log::error!("{err}");
+        }
+
+        self
+    }
+
+    fn try_add_dsp_source<D: DspGraph>(
+        &mut self,
+        dsp_graph: D,
+        source_type: SourceType,
+    ) -> Result<&mut Self, DspError> {
         let mut dsp_manager = self.world.resource_mut::<DspManager>();
 
-        dsp_manager.add_graph(dsp_graph, source_type);
+        dsp_manager.add_graph(dsp_graph, source_type)?;
+
+        Ok(self)
+    }
+
+    fn add_param_dsp_source<P, D>(&mut self, dsp_graph: D, source_type: SourceType) -> &mut Self
+    where
+        P: Send + Sync + 'static,
+        D: ParamDspGraph<P>,
+    {
+        if let Err(err) = self.try_add_param_dsp_source(dsp_graph, source_type) {
+            bevy::log::error!("{err}");
+        }
 
         self
     }
+
+    fn try_add_param_dsp_source<P, D>(
+        &mut self,
+        dsp_graph: D,
+        source_type: SourceType,
+    ) -> Result<&mut Self, DspError>
+    where
+        P: Send + Sync + 'static,
+        D: ParamDspGraph<P>,
+    {
+        let mut dsp_manager = self.world.resource_mut::<DspManager>();
+
+        dsp_manager.add_param_graph(dsp_graph, source_type)?;
+
+        Ok(self)
+    }
+}
+
+/// The sample rate used by [`DspPlugin::default`]
+/// when the default output device cannot be probed.
+pub const FALLBACK_SAMPLE_RATE: f32 = 44100.0;
+
+/// An error that occurs when probing or opening the default output device.
+#[derive(Debug)]
+pub enum DeviceError {
+    /// There is no default output device available.
+    NoOutputDevice,
+    /// The default stream config of the output device cannot be found.
+    DefaultStreamConfig(cpal::DefaultStreamConfigError),
+    /// The output stream cannot be built.
+    BuildStream(cpal::BuildStreamError),
+    /// The output stream cannot be played.
+    PlayStream(cpal::PlayStreamError),
+}
+
+impl Display for DeviceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceError::NoOutputDevice => write!(f, "No output device available."),
+            DeviceError::DefaultStreamConfig(err) => {
+                write!(f, "Cannot find default stream config. Error: {err}")
+            }
+            DeviceError::BuildStream(err) => write!(f, "Cannot build output stream. Error: {err}"),
+            DeviceError::PlayStream(err) => write!(f, "Cannot play output stream. Error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeviceError::NoOutputDevice => None,
+            DeviceError::DefaultStreamConfig(err) => Some(err),
+            DeviceError::BuildStream(err) => Some(err),
+            DeviceError::PlayStream(err) => Some(err),
+        }
+    }
 }
 
-static DEFAULT_SAMPLE_RATE: Lazy<f32> = Lazy::new(default_sample_rate);
+static DEFAULT_SAMPLE_RATE: LazyLock<f32> = LazyLock::new(|| sample_rate_or(FALLBACK_SAMPLE_RATE));
+
+fn sample_rate_or(fallback_sample_rate: f32) -> f32 {
+    device_sample_rate().unwrap_or_else(|err| {
+        bevy::log::warn!(
+            "Cannot get the sample rate of the output device, \
+            falling back to {fallback_sample_rate} Hz. Error: {err}"
+        );
+        fallback_sample_rate
+    })
+}
 
 #[cfg(not(test))]
-fn default_sample_rate() -> f32 {
+fn device_sample_rate() -> Result<f32, DeviceError> {
     use cpal::traits::{DeviceTrait, HostTrait};
 
     let host = cpal::default_host();
     let device = host
         .default_output_device()
-        .unwrap_or_else(|| panic!("No output device available."));
+        .ok_or(DeviceError::NoOutputDevice)?;
     let default_config = device
         .default_output_config()
-        .unwrap_or_else(|err| panic!("Cannot find default stream config. Error: {err}"));
+        .map_err(DeviceError::DefaultStreamConfig)?;
 
     #[allow(clippy::cast_precision_loss)]
-    {
-        default_config.sample_rate().0 as f32
-    }
+    let sample_rate = default_config.sample_rate().0 as f32;
+
+    Ok(sample_rate)
 }
 
 #[cfg(test)]
-fn default_sample_rate() -> f32 {
+#[allow(clippy::unnecessary_wraps)]
+fn device_sample_rate() -> Result<f32, DeviceError> {
     bevy::log::warn!("This is in test mode!");
-    44100.0
+    Ok(44100.0)
 }
 
 /// Prelude for all `bevy_fundsp` types.
@@ -159,14 +437,37 @@ fn default_sample_rate() -> f32 {
 pub mod prelude {
     pub use {
         crate::{
+            audio_clock::{AudioClock, SourceClock},
             backend::{Backend, DefaultBackend, DspAudioExt},
-            dsp_graph::DspGraph,
+            controls::Controls,
+            dsp_bus::{BusSettings, DspBus, DspBuses},
+            dsp_emitter::{DspEmitter, DspListener},
+            dsp_error::DspError,
+            dsp_graph::{
+                controllable, named_graph_id, Controllable, DspGraph, Named, ParamDspGraph,
+            },
             dsp_manager::DspManager,
-            dsp_source::{DspSource, Iter, IterMono, SourceType},
-            DspAppExt, DspPlugin,
+            dsp_source::{
+                Channels, Completion, DspSource, Iter, IterInterleaved, IterMono, ResampleQuality,
+                SampleFormat, SourceType,
+            },
+            static_renderer::{RenderId, StaticRenderFinished, StaticRenderer},
+            voice_allocator::{note_to_hz, VoiceAllocator, VoiceStealing},
+            DeviceError, DspAppExt, DspPlugin,
         },
         fundsp::hacker32::*,
     };
+
+    #[cfg(feature = "bevy_audio")]
+    pub use crate::backend::bevy_audio::{DspCommandsExt, DspPlayError};
+
+    #[cfg(feature = "kira")]
+    pub use crate::backend::kira::{
+        DspChannelHandle, DspSoundData, DspSoundHandle, KiraBuses, KiraPlaybackSettings,
+    };
+
+    #[cfg(feature = "midi")]
+    pub use crate::midi::{MidiEvent, MidiFile, MidiInstruments, MidiMessage, MidiPlayer};
 }
 
 #[doc = include_str!("../README.md")]
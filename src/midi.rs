@@ -0,0 +1,569 @@
+//! Module for playing Standard MIDI files through DSP instruments.
+//!
+//! This is only available with the `midi` feature.
+
+use {
+    crate::{dsp_error::DspError, dsp_source::DspSource, voice_allocator::VoiceAllocator},
+    bevy::{
+        asset::{AssetLoader, LoadContext, LoadedAsset},
+        prelude::{Component, Query, Res, Time},
+        reflect::{TypePath, TypeUuid},
+        utils::{BoxedFuture, HashMap},
+    },
+    midly::{Format, MetaMessage, Smf, Timing, TrackEventKind},
+    std::sync::Arc,
+};
+
+/// The default tempo of MIDI files, in microseconds per beat.
+const DEFAULT_TEMPO: u32 = 500_000;
+
+/// A Standard MIDI file, loaded from `.mid` and `.midi` files.
+///
+/// The timing of each event is converted into seconds,
+/// respecting every tempo change.
+/// Play it using a [`MidiPlayer`].
+#[derive(Debug, Clone, TypeUuid, TypePath)]
+#[uuid = "4c8b3f5e-2d7a-4e91-b6f0-9a1c5e8d3b27"]
+pub struct MidiFile {
+    events: Arc<[MidiEvent]>,
+}
+
+/// An event in a [`MidiFile`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MidiEvent {
+    /// The time of the event in seconds, from the start of the file.
+    pub time: f64,
+    /// The MIDI channel of the event, from 0 to 15.
+    pub channel: u8,
+    /// What the event does.
+    pub message: MidiMessage,
+}
+
+/// The MIDI messages that can drive DSP instruments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiMessage {
+    /// Start playing a note.
+    NoteOn {
+        /// The MIDI note.
+        note: u8,
+        /// The velocity from 0 to 1.
+        velocity: f32,
+    },
+    /// Stop playing a note.
+    NoteOff {
+        /// The MIDI note.
+        note: u8,
+    },
+    /// Change the value of a controller.
+    ControlChange {
+        /// The controller number.
+        controller: u8,
+        /// The value from 0 to 1.
+        value: f32,
+    },
+    /// Change the program of the channel.
+    ProgramChange {
+        /// The program number.
+        program: u8,
+    },
+}
+
+impl MidiFile {
+    /// Parse a Standard MIDI file from its bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DspError::Decode`] if the bytes are not a valid MIDI file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DspError> {
+        let smf = Smf::parse(bytes).map_err(|err| DspError::Decode(Box::new(err)))?;
+
+        // Every event with its absolute tick.
+        // Sequential tracks are played one after another,
+        // otherwise every track starts at the same time.
+        let mut events = Vec::new();
+        let mut track_start = 0_u64;
+
+        for (track_index, track) in smf.tracks.iter().enumerate() {
+            let mut tick = track_start;
+
+            for (event_index, event) in track.iter().enumerate() {
+                tick += u64::from(event.delta.as_int());
+                events.push((tick, track_index, event_index, event.kind));
+            }
+
+            if smf.header.format == Format::Sequential {
+                track_start = tick;
+            }
+        }
+
+        // Tempo changes are usually in the first track,
+        // so they come before the notes of other tracks at the same tick.
+        events.sort_by_key(|&(tick, track_index, event_index, _)| (tick, track_index, event_index));
+
+        let mut tempo = DEFAULT_TEMPO;
+        let mut last_tick = 0;
+        let mut time = 0.0;
+
+        let seconds_per_tick = |tempo: u32| match smf.header.timing {
+            Timing::Metrical(ticks_per_beat) => {
+                f64::from(tempo) / 1_000_000.0 / f64::from(ticks_per_beat.as_int().max(1))
+            }
+            Timing::Timecode(fps, subframes) => {
+                1.0 / f64::from(fps.as_f32()) / f64::from(subframes.max(1))
+            }
+        };
+
+        let events = events
+            .into_iter()
+            .filter_map(|(tick, _, _, kind)| {
+                #[allow(clippy::cast_precision_loss)]
+                let elapsed = (tick - last_tick) as f64;
+                time += elapsed * seconds_per_tick(tempo);
+                last_tick = tick;
+
+                match kind {
+                    TrackEventKind::Meta(MetaMessage::Tempo(new_tempo)) => {
+                        tempo = new_tempo.as_int();
+                        None
+                    }
+                    TrackEventKind::Midi { channel, message } => MidiMessage::from_midly(message)
+                        .map(|message| MidiEvent {
+                            time,
+                            channel: channel.as_int(),
+                            message,
+                        }),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        Ok(Self { events })
+    }
+
+    /// The events of the MIDI file, sorted by time.
+    #[must_use]
+    pub fn events(&self) -> &[MidiEvent] {
+        &self.events
+    }
+
+    /// The time of the last event in seconds.
+    #[must_use]
+    pub fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |event| event.time)
+    }
+}
+
+impl MidiMessage {
+    fn from_midly(message: midly::MidiMessage) -> Option<Self> {
+        let to_unit = |value: midly::num::u7| f32::from(value.as_int()) / 127.0;
+
+        match message {
+            midly::MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => Some(Self::NoteOn {
+                note: key.as_int(),
+                velocity: to_unit(vel),
+            }),
+            midly::MidiMessage::NoteOn { key, .. } | midly::MidiMessage::NoteOff { key, .. } => {
+                Some(Self::NoteOff { note: key.as_int() })
+            }
+            midly::MidiMessage::Controller { controller, value } => Some(Self::ControlChange {
+                controller: controller.as_int(),
+                value: to_unit(value),
+            }),
+            midly::MidiMessage::ProgramChange { program } => Some(Self::ProgramChange {
+                program: program.as_int(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Loads [`MidiFile`]s from `.mid` and `.midi` files.
+#[derive(Default)]
+pub struct MidiFileLoader;
+
+impl AssetLoader for MidiFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let midi_file = MidiFile::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(midi_file));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["mid", "midi"]
+    }
+}
+
+/// The DSP instruments that play each MIDI channel and program.
+///
+/// Notes are played by the instrument of the current program of their channel.
+/// If there is none, the instrument of the channel is used instead.
+/// Notes without an instrument are ignored.
+///
+/// Controllers are mapped to parameters of the [`Controls`](crate::controls::Controls)
+/// of every voice in the instrument, with values from 0 to 1.
+#[derive(Default)]
+pub struct MidiInstruments {
+    channels: HashMap<u8, VoiceAllocator>,
+    programs: HashMap<u8, VoiceAllocator>,
+    channel_programs: HashMap<u8, u8>,
+    controls: HashMap<u8, String>,
+}
+
+/// Which instrument plays a note.
+#[derive(Clone, Copy)]
+enum Instrument {
+    Channel(u8),
+    Program(u8),
+}
+
+impl MidiInstruments {
+    /// Create an empty set of instruments.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Play the notes of the given MIDI channel, from 0 to 15,
+    /// with the given voices.
+    #[must_use]
+    pub fn with_channel(mut self, channel: u8, voice_allocator: VoiceAllocator) -> Self {
+        self.channels.insert(channel, voice_allocator);
+        self
+    }
+
+    /// Play the notes of the given MIDI program with the given voices.
+    #[must_use]
+    pub fn with_program(mut self, program: u8, voice_allocator: VoiceAllocator) -> Self {
+        self.programs.insert(program, voice_allocator);
+        self
+    }
+
+    /// Map the given MIDI controller to the parameter with the given name.
+    #[must_use]
+    pub fn with_control(mut self, controller: u8, name: &str) -> Self {
+        self.controls.insert(controller, name.to_owned());
+        self
+    }
+
+    /// The DSP sources of every voice of every instrument.
+    ///
+    /// Each of these must be played once.
+    pub fn voices(&self) -> impl Iterator<Item = &DspSource> {
+        self.channels
+            .values()
+            .chain(self.programs.values())
+            .flat_map(VoiceAllocator::voices)
+    }
+
+    /// Play the given MIDI message on the given channel.
+    pub fn handle(&mut self, channel: u8, message: MidiMessage) {
+        match message {
+            MidiMessage::NoteOn { note, velocity } => {
+                if let Some(voice_allocator) = self.instrument(channel) {
+                    voice_allocator.note_on(note, velocity);
+                }
+            }
+            MidiMessage::NoteOff { note } => {
+                if let Some(voice_allocator) = self.instrument(channel) {
+                    voice_allocator.note_off(note);
+                }
+            }
+            MidiMessage::ControlChange { controller, value } => {
+                let Some(name) = self.controls.get(&controller).cloned() else {
+                    return;
+                };
+
+                if let Some(voice_allocator) = self.instrument(channel) {
+                    voice_allocator.set(&name, value);
+                }
+            }
+            MidiMessage::ProgramChange { program } => {
+                self.channel_programs.insert(channel, program);
+            }
+        }
+    }
+
+    /// Release every held note of every instrument.
+    pub fn all_notes_off(&mut self) {
+        for voice_allocator in self.channels.values_mut().chain(self.programs.values_mut()) {
+            voice_allocator.all_notes_off();
+        }
+    }
+
+    fn instrument(&mut self, channel: u8) -> Option<&mut VoiceAllocator> {
+        let instrument = match self.channel_programs.get(&channel) {
+            Some(program) if self.programs.contains_key(program) => Instrument::Program(*program),
+            _ => Instrument::Channel(channel),
+        };
+
+        match instrument {
+            Instrument::Channel(channel) => self.channels.get_mut(&channel),
+            Instrument::Program(program) => self.programs.get_mut(&program),
+        }
+    }
+}
+
+/// Plays a [`MidiFile`] through [`MidiInstruments`].
+///
+/// When added to an entity, the player advances with [`Time`].
+/// The voices of the instruments must be played separately,
+/// see [`MidiInstruments::voices`].
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// fn piano() -> impl AudioUnit32 {
+///     // The inputs are the frequency and the gate.
+///     sine() * adsr_live(0.01, 0.5, 0.3, 0.5) >> split::<U2>() * 0.2
+/// }
+///
+/// fn play_song(
+///     mut commands: Commands,
+///     dsp_manager: Res<DspManager>,
+///     midi_files: Res<Assets<MidiFile>>,
+///     song: Res<Song>,
+/// ) {
+///     let (Ok(piano), Some(midi_file)) = (dsp_manager.try_get_graph(piano), midi_files.get(&song.0)) else {
+///         return;
+///     };
+///
+///     let instruments = MidiInstruments::new().with_channel(0, VoiceAllocator::new(&piano, 16));
+///
+///     for voice in instruments.voices() {
+///         // Play the voice using the backend...
+///     }
+///
+///     commands.spawn(MidiPlayer::new(midi_file, instruments));
+/// }
+/// # #[derive(Resource)]
+/// # struct Song(Handle<MidiFile>);
+/// ```
+#[derive(Component)]
+pub struct MidiPlayer {
+    events: Arc<[MidiEvent]>,
+    instruments: MidiInstruments,
+    /// The index of the next event.
+    position: usize,
+    time: f64,
+    paused: bool,
+}
+
+impl MidiPlayer {
+    /// Create a player for the given MIDI file.
+    #[must_use]
+    pub fn new(midi_file: &MidiFile, instruments: MidiInstruments) -> Self {
+        Self {
+            events: midi_file.events.clone(),
+            instruments,
+            position: 0,
+            time: 0.0,
+            paused: false,
+        }
+    }
+
+    /// The instruments played by this player.
+    #[must_use]
+    pub fn instruments(&self) -> &MidiInstruments {
+        &self.instruments
+    }
+
+    /// The current time in seconds.
+    #[must_use]
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Whether every event has been played.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.position == self.events.len()
+    }
+
+    /// Pause the player, releasing every held note.
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.instruments.all_notes_off();
+    }
+
+    /// Resume the player.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Whether the player is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Advance the player by the given number of seconds,
+    /// playing every event until then.
+    pub fn advance(&mut self, seconds: f64) {
+        if self.paused {
+            return;
+        }
+
+        self.time += seconds;
+
+        while let Some(event) = self.events.get(self.position) {
+            if event.time > self.time {
+                break;
+            }
+
+            self.instruments.handle(event.channel, event.message);
+            self.position += 1;
+        }
+    }
+
+    /// Render the MIDI file for the given duration in seconds,
+    /// without a backend nor an audio device.
+    ///
+    /// Each event is played at the exact frame it happens.
+    /// This renders the voices one frame at a time, so it is slow.
+    #[must_use]
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    pub fn render(mut self, sample_rate: f32, duration: f32) -> Vec<[f32; 2]> {
+        let number_of_frames = (f64::from(sample_rate) * f64::from(duration)).round() as usize;
+        let frame_duration = 1.0 / f64::from(sample_rate);
+
+        let mut voices: Vec<_> = self
+            .instruments
+            .voices()
+            .map(|voice| {
+                let mut voice = voice.clone().with_block_size(1);
+                voice.sample_rate = sample_rate;
+                voice.into_iter()
+            })
+            .collect();
+
+        // The events at time 0 are played before the first frame.
+        self.advance(0.0);
+
+        (0..number_of_frames)
+            .map(|_| {
+                let frame = voices.iter_mut().fold([0.0; 2], |[left, right], voice| {
+                    let [voice_left, voice_right] = voice.next().unwrap_or_default();
+                    [left + voice_left, right + voice_right]
+                });
+
+                self.advance(frame_duration);
+
+                frame
+            })
+            .collect()
+    }
+}
+
+/// Advance every [`MidiPlayer`] with [`Time`].
+pub(crate) fn advance_midi_players(time: Option<Res<Time>>, mut players: Query<&mut MidiPlayer>) {
+    let Some(time) = time else {
+        return;
+    };
+
+    for mut player in &mut players {
+        player.advance(time.delta_seconds_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports, clippy::float_cmp)]
+
+    use {
+        super::{MidiFile, MidiInstruments, MidiMessage, MidiPlayer},
+        crate::{
+            dsp_source::{DspSource, SourceType},
+            voice_allocator::VoiceAllocator,
+        },
+        fundsp::hacker32::*,
+        midly::{
+            num::{u15, u24, u28, u4, u7},
+            Format, Header, MetaMessage, Smf, Timing, TrackEvent, TrackEventKind,
+        },
+    };
+
+    fn note(delta: u32, key: u8, vel: u8) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: u28::new(delta),
+            kind: TrackEventKind::Midi {
+                channel: u4::new(0),
+                message: midly::MidiMessage::NoteOn {
+                    key: u7::new(key),
+                    vel: u7::new(vel),
+                },
+            },
+        }
+    }
+
+    /// A4 for one beat at 120 BPM, then A3 for one beat at 60 BPM.
+    fn midi_bytes() -> Vec<u8> {
+        let mut smf = Smf::new(Header::new(
+            Format::SingleTrack,
+            Timing::Metrical(u15::new(480)),
+        ));
+
+        smf.tracks.push(vec![
+            note(0, 69, 127),
+            note(480, 69, 0),
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(1_000_000))),
+            },
+            note(0, 57, 127),
+            note(480, 57, 0),
+            TrackEvent {
+                delta: u28::new(0),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            },
+        ]);
+
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn tempo_changes() {
+        let midi_file = MidiFile::from_bytes(&midi_bytes()).unwrap();
+        let events = midi_file.events();
+
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0].message,
+            MidiMessage::NoteOn {
+                note: 69,
+                velocity: 1.0
+            }
+        );
+        assert_eq!(events[1].message, MidiMessage::NoteOff { note: 69 });
+        assert!((events[1].time - 0.5).abs() < 1e-9);
+        assert!((events[2].time - 0.5).abs() < 1e-9);
+        assert!((events[3].time - 1.5).abs() < 1e-9);
+        assert!((midi_file.duration() - 1.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn render_offline() {
+        let midi_file = MidiFile::from_bytes(&midi_bytes()).unwrap();
+
+        // The frequency times the gate.
+        let dsp_source = DspSource::new(|| pass() * pass(), 1000.0, SourceType::Dynamic);
+        let instruments =
+            MidiInstruments::new().with_channel(0, VoiceAllocator::new(&dsp_source, 2));
+
+        let frames = MidiPlayer::new(&midi_file, instruments).render(1000.0, 2.0);
+
+        assert_eq!(frames.len(), 2000);
+        assert_eq!(frames[0], [440.0, 440.0]);
+        assert_eq!(frames[499], [440.0, 440.0]);
+        assert_eq!(frames[501], [220.0, 220.0]);
+        assert_eq!(frames[1499], [220.0, 220.0]);
+        assert_eq!(frames[1501], [0.0, 0.0]);
+    }
+}
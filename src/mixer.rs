@@ -0,0 +1,645 @@
+//! Module for [`Mixer`],
+//! which mixes playing DSP sources into a single stereo output.
+
+use {
+    crate::{
+        audio_clock::AudioClock,
+        dsp_bus::{DspBus, DspBuses},
+        dsp_error::DspError,
+        dsp_source::{DspSource, Iter, ResampleQuality, Source, SourceType},
+        resampler::Resampler,
+    },
+    bevy::reflect::{TypePath, TypeUuid},
+    fundsp::hacker32::{AudioUnit32, Shared},
+    std::sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender, SyncSender},
+        Arc,
+    },
+};
+
+/// Mixes playing DSP sources into a single stereo output.
+///
+/// The DSP sources are resampled to the sample rate of the mixer.
+/// Static DSP sources are rendered when played,
+/// while dynamic DSP sources are rendered as the output is mixed.
+///
+/// Each DSP source is mixed into its bus,
+/// which then feeds into the other buses and the output,
+/// see [`DspBuses`].
+///
+/// This is used by the backends that do not rely on another audio plugin,
+/// such as the [`HeadlessBackend`](crate::backend::headless::HeadlessBackend).
+pub struct Mixer {
+    sample_rate: f32,
+    sounds: Vec<MixerSound>,
+    /// Ordered so that each bus comes before the bus it feeds into.
+    buses: Vec<MixerBus>,
+    /// Receives the commands sent from other threads.
+    queue: Option<Receiver<MixerCommand>>,
+    /// Sends the removed sounds back to the thread of the queue,
+    /// so they are not dropped while mixing.
+    released: Option<SyncSender<MixerSound>>,
+    /// The clock advanced by the mixed frames, if any.
+    audio_clock: Option<AudioClock>,
+    /// The number of frames mixed so far.
+    mixed_frames: u64,
+}
+
+/// A static DSP source rendered into frames,
+/// played by the backends that use a [`Mixer`].
+#[derive(TypeUuid, TypePath)]
+#[uuid = "042c2401-3e6b-42d3-bf96-e626c6fbc723"]
+#[allow(clippy::module_name_repetitions)]
+pub struct MixerAudioSource {
+    frames: Arc<[[f32; 2]]>,
+    sample_rate: f32,
+    resample_quality: ResampleQuality,
+    bus: DspBus,
+}
+
+impl MixerAudioSource {
+    pub(crate) fn new(dsp_source: &DspSource) -> Result<Self, DspError> {
+        Ok(Self {
+            frames: dsp_source.to_frames()?,
+            sample_rate: dsp_source.sample_rate,
+            resample_quality: dsp_source.resample_quality,
+            bus: dsp_source.bus.clone(),
+        })
+    }
+
+    /// The rendered stereo frames.
+    #[must_use]
+    pub fn frames(&self) -> &[[f32; 2]] {
+        &self.frames
+    }
+
+    /// The sample rate of the frames.
+    #[must_use]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+}
+
+/// The ID of a sound played by a [`Mixer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundId(u64);
+
+/// The number of frames mixed at once.
+///
+/// The buffers of the buses hold this many frames,
+/// so mixing does not allocate.
+pub(crate) const MIX_BUFFER_SIZE: usize = 512;
+
+/// The number of removed sounds that can wait to be received
+/// from the queue of a [`Mixer`] before they are dropped while mixing.
+const MAX_RELEASED_SOUNDS: usize = 256;
+
+/// The ID of the next sound, shared by every mixer
+/// so sounds can be prepared before they reach their mixer.
+static NEXT_SOUND_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A sound played by a [`Mixer`].
+///
+/// This can be prepared on another thread,
+/// so the mixer is not locked while the DSP source is rendered.
+pub(crate) struct MixerSound {
+    id: SoundId,
+    kind: SoundKind,
+    bus: DspBus,
+    /// The index of the bus in the mixer, resolved when the sound is added.
+    bus_index: usize,
+    resampler: Resampler,
+    /// The number of source frames per output frame.
+    step: f64,
+    volume: f32,
+    paused: bool,
+}
+
+/// A command sent to a [`Mixer`] through its queue.
+#[cfg_attr(not(feature = "cpal_output"), allow(dead_code))]
+pub(crate) enum MixerCommand {
+    Play(MixerSound),
+    Stop(SoundId),
+    StopAll,
+    Pause(SoundId),
+    Resume(SoundId),
+    SetVolume(SoundId, f32),
+}
+
+enum SoundKind {
+    Static {
+        frames: Arc<[[f32; 2]]>,
+        /// The index of the next frame to be resampled.
+        position: usize,
+    },
+    Dynamic(Box<Iter>),
+}
+
+impl MixerSound {
+    /// Prepare the given DSP source to be played at the given sample rate.
+    ///
+    /// Static DSP sources are rendered,
+    /// while dynamic DSP sources get a new DSP graph.
+    pub(crate) fn new(dsp_source: &DspSource, output_sample_rate: f32) -> Result<Self, DspError> {
+        let kind = match dsp_source.source_type {
+            SourceType::Static { .. } => SoundKind::Static {
+                frames: dsp_source.to_frames()?,
+                position: 0,
+            },
+            SourceType::Dynamic => SoundKind::Dynamic(Box::new(dsp_source.clone().into_iter())),
+        };
+
+        Ok(Self::with_kind(
+            kind,
+            dsp_source.bus.clone(),
+            dsp_source.sample_rate,
+            dsp_source.resample_quality,
+            output_sample_rate,
+        ))
+    }
+
+    /// Prepare the given rendered DSP source to be played at the given sample rate.
+    pub(crate) fn from_audio_source(
+        audio_source: &MixerAudioSource,
+        output_sample_rate: f32,
+    ) -> Self {
+        Self::with_kind(
+            SoundKind::Static {
+                frames: audio_source.frames.clone(),
+                position: 0,
+            },
+            audio_source.bus.clone(),
+            audio_source.sample_rate,
+            audio_source.resample_quality,
+            output_sample_rate,
+        )
+    }
+
+    fn with_kind(
+        kind: SoundKind,
+        bus: DspBus,
+        sample_rate: f32,
+        resample_quality: ResampleQuality,
+        output_sample_rate: f32,
+    ) -> Self {
+        Self {
+            id: SoundId(NEXT_SOUND_ID.fetch_add(1, Ordering::Relaxed)),
+            kind,
+            bus,
+            bus_index: 0,
+            resampler: Resampler::new(resample_quality),
+            step: f64::from(sample_rate) / f64::from(output_sample_rate),
+            volume: 1.0,
+            paused: false,
+        }
+    }
+
+    /// The ID the sound is played with.
+    #[cfg_attr(not(feature = "cpal_output"), allow(dead_code))]
+    pub(crate) fn id(&self) -> SoundId {
+        self.id
+    }
+
+    fn next_frame(&mut self) -> [f32; 2] {
+        let [left, right] = match &mut self.kind {
+            SoundKind::Static { frames, position } => self.resampler.next_frame(self.step, || {
+                let frame = frames.get(*position).copied().unwrap_or_default();
+                *position += 1;
+                frame
+            }),
+            SoundKind::Dynamic(iter) => self.resampler.next_frame(self.step, || iter.sample()),
+        };
+
+        [left * self.volume, right * self.volume]
+    }
+
+    fn is_finished(&self) -> bool {
+        match &self.kind {
+            // The resampler reads a few frames ahead.
+            SoundKind::Static { frames, position } => *position >= frames.len() + 3,
+            SoundKind::Dynamic(iter) => iter.is_finished(),
+        }
+    }
+}
+
+struct MixerBus {
+    bus: DspBus,
+    gain: Shared<f32>,
+    effect: Option<Box<dyn AudioUnit32>>,
+    /// The index of the bus this bus feeds into,
+    /// or `None` if it feeds into the output.
+    output: Option<usize>,
+    buffer: Vec<[f32; 2]>,
+}
+
+impl MixerBus {
+    /// Apply the effect and gain to the first mixed frames.
+    fn process(&mut self, size: usize) {
+        let gain = self.gain.value();
+
+        for frame in &mut self.buffer[..size] {
+            if let Some(effect) = &mut self.effect {
+                let mut output = [0.0; 2];
+                effect.tick(&frame[..], &mut output);
+                *frame = output;
+            }
+
+            *frame = [frame[0] * gain, frame[1] * gain];
+        }
+    }
+}
+
+impl Mixer {
+    /// Create a mixer that outputs at the given sample rate,
+    /// with the default [`DspBuses`].
+    #[must_use]
+    pub fn new(sample_rate: f32) -> Self {
+        let mut mixer = Self {
+            sample_rate,
+            sounds: Vec::new(),
+            buses: Vec::new(),
+            queue: None,
+            released: None,
+            audio_clock: None,
+            mixed_frames: 0,
+        };
+
+        mixer.set_buses(&DspBuses::default());
+        mixer
+    }
+
+    /// Route the DSP sources through the given buses.
+    ///
+    /// The effects of the buses are generated again.
+    /// The gains are shared with the given buses.
+    pub fn set_buses(&mut self, buses: &DspBuses) {
+        let sample_rate = f64::from(self.sample_rate);
+
+        self.buses = buses
+            .routing()
+            .into_iter()
+            .map(|(bus, settings, output)| MixerBus {
+                bus,
+                gain: settings.shared_gain(),
+                effect: settings.generate_effect().map(|mut effect| {
+                    effect.set_sample_rate(sample_rate);
+                    effect
+                }),
+                output,
+                buffer: vec![[0.0; 2]; MIX_BUFFER_SIZE],
+            })
+            .collect();
+
+        for sound in &mut self.sounds {
+            sound.bus_index = bus_index(&self.buses, &sound.bus);
+        }
+    }
+
+    /// Advance the given clock by the mixed frames,
+    /// instead of the frames rendered by the DSP sources.
+    pub(crate) fn set_audio_clock(&mut self, audio_clock: AudioClock) {
+        audio_clock.drive_by_output();
+        self.audio_clock = Some(audio_clock);
+    }
+
+    /// The sample rate of the output.
+    #[must_use]
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Play the given DSP source.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`DspError`] if the static DSP source cannot be rendered.
+    pub fn play(&mut self, dsp_source: &DspSource) -> Result<SoundId, DspError> {
+        let sound = MixerSound::new(dsp_source, self.sample_rate)?;
+        Ok(self.add(sound))
+    }
+
+    /// Play the given rendered DSP source.
+    pub fn play_audio_source(&mut self, audio_source: &MixerAudioSource) -> SoundId {
+        let sound = MixerSound::from_audio_source(audio_source, self.sample_rate);
+        self.add(sound)
+    }
+
+    /// Play the given prepared sound.
+    pub(crate) fn add(&mut self, mut sound: MixerSound) -> SoundId {
+        let id = sound.id;
+        sound.bus_index = bus_index(&self.buses, &sound.bus);
+        self.sounds.push(sound);
+        id
+    }
+
+    /// Create a queue to control the mixer from other threads
+    /// without waiting for it.
+    ///
+    /// The queued commands are applied the next time the mixer mixes
+    /// or [`Mixer::receive_queued`] is called.
+    /// The sounds removed from the mixer afterwards are sent back through the returned receiver,
+    /// so they can be dropped outside of the thread that mixes.
+    /// Only the last queue created is received from.
+    #[cfg_attr(not(feature = "cpal_output"), allow(dead_code))]
+    pub(crate) fn queue(&mut self) -> (Sender<MixerCommand>, Receiver<MixerSound>) {
+        let (sender, receiver) = mpsc::channel();
+        let (released_sender, released_receiver) = mpsc::sync_channel(MAX_RELEASED_SOUNDS);
+
+        self.queue = Some(receiver);
+        self.released = Some(released_sender);
+
+        (sender, released_receiver)
+    }
+
+    /// Apply the commands sent through the queue.
+    pub(crate) fn receive_queued(&mut self) {
+        let Some(queue) = self.queue.take() else {
+            return;
+        };
+
+        for command in queue.try_iter() {
+            match command {
+                MixerCommand::Play(sound) => {
+                    self.add(sound);
+                }
+                MixerCommand::Stop(id) => self.stop(id),
+                MixerCommand::StopAll => self.stop_all(),
+                MixerCommand::Pause(id) => self.pause(id),
+                MixerCommand::Resume(id) => self.resume(id),
+                MixerCommand::SetVolume(id, volume) => self.set_volume(id, volume),
+            }
+        }
+
+        self.queue = Some(queue);
+    }
+
+    /// Stop the given sound.
+    pub fn stop(&mut self, id: SoundId) {
+        self.remove_sounds(|sound| sound.id == id);
+    }
+
+    /// Stop every sound.
+    pub fn stop_all(&mut self) {
+        self.remove_sounds(|_| true);
+    }
+
+    /// Pause the given sound.
+    pub fn pause(&mut self, id: SoundId) {
+        if let Some(sound) = self.sound_mut(id) {
+            sound.paused = true;
+        }
+    }
+
+    /// Resume the given sound if it is paused.
+    pub fn resume(&mut self, id: SoundId) {
+        if let Some(sound) = self.sound_mut(id) {
+            sound.paused = false;
+        }
+    }
+
+    /// Set the volume of the given sound as an amplitude,
+    /// where 1 is the original volume.
+    pub fn set_volume(&mut self, id: SoundId, volume: f32) {
+        if let Some(sound) = self.sound_mut(id) {
+            sound.volume = volume;
+        }
+    }
+
+    /// Whether the given sound is still playing or paused.
+    #[must_use]
+    pub fn is_playing(&self, id: SoundId) -> bool {
+        self.sounds.iter().any(|sound| sound.id == id)
+    }
+
+    /// The number of sounds that are playing or paused.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sounds.len()
+    }
+
+    /// Whether there are no sounds playing or paused.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sounds.is_empty()
+    }
+
+    /// Mix the next frames of every playing sound into the given buffer.
+    ///
+    /// The buffer is overwritten.
+    /// Finished sounds are removed afterwards.
+    pub fn mix(&mut self, output: &mut [[f32; 2]]) {
+        self.receive_queued();
+
+        for chunk in output.chunks_mut(MIX_BUFFER_SIZE) {
+            self.mix_chunk(chunk);
+        }
+
+        self.remove_sounds(MixerSound::is_finished);
+
+        self.mixed_frames += output.len() as u64;
+
+        if let Some(audio_clock) = &self.audio_clock {
+            audio_clock.set_output_frames(self.mixed_frames, self.sample_rate);
+        }
+    }
+
+    /// Mix at most [`MIX_BUFFER_SIZE`] frames into the given buffer.
+    fn mix_chunk(&mut self, output: &mut [[f32; 2]]) {
+        let size = output.len();
+
+        for bus in &mut self.buses {
+            bus.buffer[..size].fill([0.0; 2]);
+        }
+
+        for sound in self.sounds.iter_mut().filter(|sound| !sound.paused) {
+            for [left, right] in &mut self.buses[sound.bus_index].buffer[..size] {
+                let frame = sound.next_frame();
+                *left += frame[0];
+                *right += frame[1];
+            }
+        }
+
+        output.fill([0.0; 2]);
+
+        for index in 0..self.buses.len() {
+            let (buses, outputs) = self.buses.split_at_mut(index + 1);
+            let bus = &mut buses[index];
+
+            bus.process(size);
+
+            let destination = match bus.output {
+                Some(output) => &mut outputs[output - index - 1].buffer[..size],
+                None => &mut *output,
+            };
+
+            for ([left, right], frame) in destination.iter_mut().zip(&bus.buffer) {
+                *left += frame[0];
+                *right += frame[1];
+            }
+        }
+    }
+
+    /// Remove the matching sounds,
+    /// sending them back through the queue if there is one.
+    ///
+    /// If the receiver is gone or full, the sounds are dropped here instead.
+    fn remove_sounds(&mut self, mut remove: impl FnMut(&MixerSound) -> bool) {
+        for sound in self.sounds.extract_if(.., |sound| remove(sound)) {
+            if let Some(released) = &self.released {
+                released.try_send(sound).ok();
+            }
+        }
+    }
+
+    fn sound_mut(&mut self, id: SoundId) -> Option<&mut MixerSound> {
+        self.sounds.iter_mut().find(|sound| sound.id == id)
+    }
+}
+
+/// The index of the given bus.
+///
+/// Sounds played through a missing bus are played through the master bus,
+/// which is always last.
+fn bus_index(buses: &[MixerBus], bus: &DspBus) -> usize {
+    buses
+        .iter()
+        .position(|other| other.bus == *bus)
+        .unwrap_or(buses.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports, clippy::float_cmp)]
+
+    use {
+        super::{Mixer, MIX_BUFFER_SIZE},
+        crate::{
+            audio_clock::AudioClock,
+            dsp_bus::{BusSettings, DspBus, DspBuses},
+            dsp_source::{DspSource, SourceType},
+        },
+        fundsp::hacker32::*,
+    };
+
+    fn short() -> impl AudioUnit32 {
+        constant(0.3)
+    }
+
+    fn endless() -> impl AudioUnit32 {
+        constant(0.5)
+    }
+
+    #[test]
+    fn sounds_are_mixed() {
+        let mut mixer = Mixer::new(100.0);
+
+        let short = DspSource::new(short, 100.0, SourceType::Static { duration: 0.1 });
+        let endless = DspSource::new(endless, 100.0, SourceType::Dynamic);
+
+        let short = mixer.play(&short).unwrap();
+        let endless = mixer.play(&endless).unwrap();
+
+        let mut output = [[0.0; 2]; 10];
+        mixer.mix(&mut output);
+
+        // Static DSP sources are rendered as 16-bit integers by default.
+        let short_sample = 9830.0 / 32768.0;
+
+        assert_eq!(output, [[short_sample + 0.5; 2]; 10]);
+
+        mixer.set_volume(endless, 0.5);
+        mixer.mix(&mut output);
+
+        assert_eq!(output, [[0.25; 2]; 10]);
+        assert!(!mixer.is_playing(short));
+        assert!(mixer.is_playing(endless));
+
+        mixer.pause(endless);
+        mixer.mix(&mut output);
+
+        assert_eq!(output, [[0.0; 2]; 10]);
+
+        mixer.stop(endless);
+
+        assert!(mixer.is_empty());
+    }
+
+    #[test]
+    fn sounds_are_routed_through_buses() {
+        let footsteps = DspBus::new("footsteps");
+        let buses = DspBuses::default()
+            .with_bus(DspBus::SFX, BusSettings::new().with_gain(0.5))
+            .with_bus(
+                footsteps.clone(),
+                BusSettings::new()
+                    .with_effect(|| (pass() * 2.0) | (pass() * 2.0))
+                    .with_output(DspBus::SFX),
+            );
+
+        let mut mixer = Mixer::new(100.0);
+        mixer.set_buses(&buses);
+
+        let music =
+            DspSource::new(|| constant(0.25), 100.0, SourceType::Dynamic).with_bus(DspBus::MUSIC);
+        let step =
+            DspSource::new(|| constant(0.25), 100.0, SourceType::Dynamic).with_bus(footsteps);
+
+        mixer.play(&music).unwrap();
+        mixer.play(&step).unwrap();
+
+        let mut output = [[0.0; 2]; 10];
+        mixer.mix(&mut output);
+
+        assert_eq!(output, [[0.5; 2]; 10]);
+
+        buses.set_gain(&DspBus::SFX, 0.0);
+        mixer.mix(&mut output);
+
+        assert_eq!(output, [[0.25; 2]; 10]);
+    }
+
+    #[test]
+    fn mixed_frames_advance_the_audio_clock() {
+        let audio_clock = AudioClock::new(200.0);
+        let mut mixer = Mixer::new(100.0);
+        mixer.set_audio_clock(audio_clock.clone());
+
+        let mut output = [[0.0; 2]; 10];
+        mixer.mix(&mut output);
+
+        assert_eq!(audio_clock.frames(), 20);
+
+        // Frames rendered by the DSP sources are ignored.
+        let dsp_source = DspSource {
+            audio_clock: Some(audio_clock.clone()),
+            ..DspSource::new(|| constant(0.5), 200.0, SourceType::Dynamic)
+        };
+        dsp_source.into_iter().take(100).for_each(drop);
+
+        assert_eq!(audio_clock.frames(), 20);
+    }
+
+    #[test]
+    fn long_buffers_are_mixed_through_the_current_buses() {
+        let mut mixer = Mixer::new(100.0);
+
+        let music =
+            DspSource::new(|| constant(0.5), 100.0, SourceType::Dynamic).with_bus(DspBus::MUSIC);
+
+        mixer.play(&music).unwrap();
+
+        let mut output = vec![[0.0; 2]; 3 * MIX_BUFFER_SIZE + 10];
+        mixer.mix(&mut output);
+
+        assert!(output.iter().all(|frame| *frame == [0.5; 2]));
+
+        // Playing sounds are routed again when the buses change.
+        mixer.set_buses(
+            &DspBuses::default().with_bus(DspBus::MUSIC, BusSettings::new().with_gain(0.5)),
+        );
+        mixer.mix(&mut output);
+
+        assert!(output.iter().all(|frame| *frame == [0.25; 2]));
+    }
+}
@@ -0,0 +1,281 @@
+//! Module for [`RenderCache`],
+//! which stores the renders of static DSP sources.
+
+use {
+    crate::{
+        dsp_error::DspError,
+        dsp_source::{Channels, SampleFormat},
+    },
+    bevy::utils::HashMap,
+    std::{
+        hash::Hash,
+        mem::size_of_val,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+    },
+    uuid::Uuid,
+};
+
+/// The default number of bytes of renders kept by a [`RenderCache`].
+pub(crate) const DEFAULT_RENDER_CACHE_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Identifies a render of a static DSP source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct RenderKey {
+    pub(crate) id: Uuid,
+    /// The bits of the sample rate, as `f32` is not hashable.
+    pub(crate) sample_rate: u32,
+    /// The bits of the duration, as `f32` is not hashable.
+    pub(crate) duration: u32,
+    pub(crate) channels: Channels,
+    pub(crate) sample_format: SampleFormat,
+    /// The hash of the values of the [`Controls`](crate::controls::Controls).
+    pub(crate) controls: u64,
+}
+
+/// Stores the renders of static DSP sources,
+/// so playing them again only costs an [`Arc`] clone.
+///
+/// This is shared between the [`DspManager`](crate::dsp_manager::DspManager)
+/// and every DSP source it creates.
+///
+/// When the renders take more than the limit in bytes,
+/// the least recently played renders are removed.
+/// Renders larger than the limit are never cached.
+pub(crate) struct RenderCache {
+    limit: usize,
+    renders: Mutex<Renders>,
+}
+
+#[derive(Default)]
+struct Renders {
+    bytes: HashMap<RenderKey, Entry<[u8]>>,
+    frames: HashMap<RenderKey, Entry<[[f32; 2]]>>,
+    /// The total size of the renders in bytes.
+    size: usize,
+    /// Incremented every time a render is used.
+    tick: u64,
+}
+
+struct Entry<T: ?Sized> {
+    render: Arc<T>,
+    last_used: u64,
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RENDER_CACHE_LIMIT)
+    }
+}
+
+impl RenderCache {
+    /// Create a cache that keeps at most `limit` bytes of renders.
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            renders: Mutex::default(),
+        }
+    }
+
+    /// Get the cached bytes, or render them if there are none.
+    pub(crate) fn bytes(
+        &self,
+        key: RenderKey,
+        render: impl FnOnce() -> Result<Arc<[u8]>, DspError>,
+    ) -> Result<Arc<[u8]>, DspError> {
+        self.get_or_render(key, |renders| &mut renders.bytes, render)
+    }
+
+    /// Get the cached frames, or render them if there are none.
+    pub(crate) fn frames(
+        &self,
+        key: RenderKey,
+        render: impl FnOnce() -> Result<Arc<[[f32; 2]]>, DspError>,
+    ) -> Result<Arc<[[f32; 2]]>, DspError> {
+        self.get_or_render(key, |renders| &mut renders.frames, render)
+    }
+
+    /// Remove every render of the DSP graph with the given ID.
+    pub(crate) fn invalidate(&self, id: &Uuid) {
+        let mut renders = self.lock();
+        let mut removed = 0;
+
+        renders.bytes.retain(|key, entry| {
+            let keep = key.id != *id;
+            if !keep {
+                removed += size_of_val(&*entry.render);
+            }
+            keep
+        });
+        renders.frames.retain(|key, entry| {
+            let keep = key.id != *id;
+            if !keep {
+                removed += size_of_val(&*entry.render);
+            }
+            keep
+        });
+
+        renders.size -= removed;
+    }
+
+    /// Remove every render.
+    pub(crate) fn clear(&self) {
+        let mut renders = self.lock();
+
+        renders.bytes.clear();
+        renders.frames.clear();
+        renders.size = 0;
+    }
+
+    /// The total size of the cached renders in bytes.
+    #[cfg(test)]
+    pub(crate) fn size(&self) -> usize {
+        self.lock().size
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Renders> {
+        self.renders.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get_or_render<T: ?Sized>(
+        &self,
+        key: RenderKey,
+        map: impl Fn(&mut Renders) -> &mut HashMap<RenderKey, Entry<T>>,
+        render: impl FnOnce() -> Result<Arc<T>, DspError>,
+    ) -> Result<Arc<T>, DspError> {
+        {
+            let mut renders = self.lock();
+            renders.tick += 1;
+            let tick = renders.tick;
+
+            if let Some(entry) = map(&mut renders).get_mut(&key) {
+                entry.last_used = tick;
+                return Ok(entry.render.clone());
+            }
+        }
+
+        // The lock is not held while rendering,
+        // so other sources can still be rendered at the same time.
+        let rendered = render()?;
+        let size = size_of_val(&*rendered);
+
+        if size > self.limit {
+            return Ok(rendered);
+        }
+
+        let mut renders = self.lock();
+        renders.tick += 1;
+        let tick = renders.tick;
+
+        if let Some(entry) = map(&mut renders).get_mut(&key) {
+            entry.last_used = tick;
+            return Ok(entry.render.clone());
+        }
+
+        renders.size += size;
+        map(&mut renders).insert(
+            key,
+            Entry {
+                render: rendered.clone(),
+                last_used: tick,
+            },
+        );
+        renders.evict(self.limit);
+
+        Ok(rendered)
+    }
+}
+
+impl Renders {
+    /// Remove the least recently used renders until they fit in the limit.
+    fn evict(&mut self, limit: usize) {
+        while self.size > limit {
+            let oldest_bytes = oldest(&self.bytes);
+            let oldest_frames = oldest(&self.frames);
+
+            let removed = match (oldest_bytes, oldest_frames) {
+                (Some((key, _)), None) => self.remove_bytes(&key),
+                (Some((key, bytes_used)), Some((_, frames_used))) if bytes_used <= frames_used => {
+                    self.remove_bytes(&key)
+                }
+                (_, Some((key, _))) => self.remove_frames(&key),
+                (None, None) => None,
+            };
+
+            match removed {
+                Some(removed) => self.size -= removed,
+                None => break,
+            }
+        }
+    }
+
+    fn remove_bytes(&mut self, key: &RenderKey) -> Option<usize> {
+        self.bytes
+            .remove(key)
+            .map(|entry| size_of_val(&*entry.render))
+    }
+
+    fn remove_frames(&mut self, key: &RenderKey) -> Option<usize> {
+        self.frames
+            .remove(key)
+            .map(|entry| size_of_val(&*entry.render))
+    }
+}
+
+/// The key and last use of the least recently used render.
+fn oldest<T: ?Sized>(renders: &HashMap<RenderKey, Entry<T>>) -> Option<(RenderKey, u64)> {
+    renders
+        .iter()
+        .min_by_key(|(_, entry)| entry.last_used)
+        .map(|(key, entry)| (*key, entry.last_used))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{RenderCache, RenderKey},
+        crate::dsp_source::{Channels, SampleFormat},
+        std::sync::Arc,
+        uuid::Uuid,
+    };
+
+    fn key(id: u128) -> RenderKey {
+        RenderKey {
+            id: Uuid::from_u128(id),
+            sample_rate: 0,
+            duration: 0,
+            channels: Channels::Stereo,
+            sample_format: SampleFormat::Int16,
+            controls: 0,
+        }
+    }
+
+    #[test]
+    fn least_recently_used_renders_are_evicted() {
+        let cache = RenderCache::new(20);
+        let render = || Ok(Arc::from(vec![0_u8; 8]));
+
+        let first = cache.bytes(key(1), render).unwrap();
+        cache.bytes(key(2), render).unwrap();
+
+        // Use the first render again, so the second is the oldest.
+        let again = cache.bytes(key(1), render).unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        cache.bytes(key(3), render).unwrap();
+
+        assert_eq!(cache.size(), 16);
+
+        let first_again = cache.bytes(key(1), render).unwrap();
+        assert!(Arc::ptr_eq(&first, &first_again));
+
+        let large = cache
+            .bytes(key(4), || Ok(Arc::from(vec![0_u8; 40])))
+            .unwrap();
+        let large_again = cache
+            .bytes(key(4), || Ok(Arc::from(vec![0_u8; 40])))
+            .unwrap();
+
+        assert!(!Arc::ptr_eq(&large, &large_again));
+        assert_eq!(cache.size(), 16);
+    }
+}
@@ -0,0 +1,365 @@
+//! Module for [`Renderer`],
+//! which renders an audio unit in blocks and serves it frame by frame.
+
+use {
+    crate::{
+        audio_clock::ClockWriter,
+        controls::{ParamEvent, ParamEvents},
+        dsp_emitter::SpatialGains,
+        dsp_source::Completion,
+    },
+    fundsp::{
+        hacker32::{AudioUnit32, Shared},
+        MAX_BUFFER_SIZE,
+    },
+    std::{collections::VecDeque, sync::Arc},
+};
+
+/// The most inputs of an audio unit that a [`Renderer`] can feed.
+const MAX_INPUTS: usize = 16;
+
+/// The most received parameter events that a [`Renderer`] keeps
+/// before applying them.
+///
+/// The queue is allocated up front, so receiving events never allocates.
+/// Further events are received after some are applied.
+const MAX_PENDING_EVENTS: usize = 256;
+
+/// Renders an [`AudioUnit32`] in blocks using [`AudioUnit32::process`].
+///
+/// This avoids dynamic dispatch on every sample,
+/// and lets FunDSP use its vectorized code paths.
+pub(crate) struct Renderer {
+    audio_unit: Box<dyn AudioUnit32>,
+    block_size: usize,
+    buffer: [[f32; MAX_BUFFER_SIZE]; 2],
+    /// The variables fed to the inputs of the audio unit,
+    /// and their buffers.
+    inputs: Vec<(Shared<f32>, [f32; MAX_BUFFER_SIZE])>,
+    /// Receives the peak amplitude of each rendered block.
+    meter: Option<Shared<f32>>,
+    events: Option<ParamEvents>,
+    /// The received events that are not applied yet, sorted by frame.
+    pending: VecDeque<ParamEvent>,
+    /// The number of frames rendered so far.
+    frame: u64,
+    /// Receives the number of frames served so far.
+    clock: Option<ClockWriter>,
+    spatial: Option<Spatial>,
+    /// The number of rendered frames in the buffer.
+    len: usize,
+    /// The index of the next frame to be served.
+    position: usize,
+    end: End,
+}
+
+/// The gains of a [`DspEmitter`](crate::dsp_emitter::DspEmitter)
+/// applied to a renderer.
+struct Spatial {
+    gains: Arc<SpatialGains>,
+    /// Whether the DSP source is played in mono,
+    /// so it is only attenuated, as there are no channels to pan.
+    mono: bool,
+    /// The gains at the end of the last rendered block,
+    /// which are ramped to the new gains to avoid clicks.
+    current: Option<[f32; 2]>,
+}
+
+impl Spatial {
+    /// The gains of the channels at the end of the next rendered block.
+    fn target(&self) -> [f32; 2] {
+        let [left, right] = self.gains.get();
+
+        if self.mono {
+            // The gains are equal power, so this is the attenuation.
+            let attenuation = left.hypot(right);
+            [attenuation; 2]
+        } else {
+            [left, right]
+        }
+    }
+}
+
+/// Tracks the [`Completion`] of a renderer.
+struct End {
+    frames_left: Option<u64>,
+    silence_threshold: f32,
+    silence_frames: Option<u64>,
+    silent_frames: u64,
+    flag: Option<Shared<f32>>,
+    finished: bool,
+}
+
+impl End {
+    #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+    fn new(completion: &Completion, sample_rate: f32) -> Self {
+        let to_frames = |seconds: f32| (f64::from(seconds) * f64::from(sample_rate)).round() as u64;
+        let frames_left = completion.max_duration.map(to_frames);
+
+        Self {
+            frames_left,
+            silence_threshold: completion.silence.map_or(0.0, |(threshold, _)| threshold),
+            silence_frames: completion.silence.map(|(_, hold)| to_frames(hold)),
+            silent_frames: 0,
+            flag: completion.flag.clone(),
+            finished: frames_left == Some(0),
+        }
+    }
+
+    fn check_flag(&mut self) {
+        if let Some(flag) = &self.flag {
+            self.finished |= flag.value() != 0.0;
+        }
+    }
+
+    fn advance(&mut self, [left, right]: [f32; 2]) {
+        if let Some(frames_left) = &mut self.frames_left {
+            *frames_left = frames_left.saturating_sub(1);
+            self.finished |= *frames_left == 0;
+        }
+
+        if let Some(silence_frames) = self.silence_frames {
+            if left.abs() <= self.silence_threshold && right.abs() <= self.silence_threshold {
+                self.silent_frames += 1;
+            } else {
+                self.silent_frames = 0;
+            }
+            self.finished |= self.silent_frames >= silence_frames;
+        }
+    }
+}
+
+impl Renderer {
+    /// Create a new renderer.
+    ///
+    /// The block size is clamped between 1 and [`MAX_BUFFER_SIZE`].
+    ///
+    /// Each input of the audio unit is read from the given variables in order,
+    /// at the start of each block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the audio unit has more inputs than the given variables
+    /// or than [`MAX_INPUTS`], or if it does not have one or two outputs.
+    pub(crate) fn new(
+        audio_unit: Box<dyn AudioUnit32>,
+        block_size: usize,
+        completion: &Completion,
+        sample_rate: f32,
+        mut inputs: Vec<Shared<f32>>,
+    ) -> Self {
+        assert!(
+            audio_unit.inputs() <= inputs.len(),
+            "DSP graphs must not have more inputs than given by `DspSource::with_inputs`."
+        );
+        assert!(
+            audio_unit.inputs() <= MAX_INPUTS,
+            "DSP graphs must not have more than {MAX_INPUTS} inputs."
+        );
+        assert!(
+            matches!(audio_unit.outputs(), 1 | 2),
+            "DSP graphs must have one or two outputs."
+        );
+
+        inputs.truncate(audio_unit.inputs());
+
+        Self {
+            audio_unit,
+            block_size: block_size.clamp(1, MAX_BUFFER_SIZE),
+            buffer: [[0.0; MAX_BUFFER_SIZE]; 2],
+            inputs: inputs
+                .into_iter()
+                .map(|input| (input, [0.0; MAX_BUFFER_SIZE]))
+                .collect(),
+            meter: None,
+            events: None,
+            pending: VecDeque::with_capacity(MAX_PENDING_EVENTS),
+            frame: 0,
+            clock: None,
+            spatial: None,
+            len: 0,
+            position: 0,
+            end: End::new(completion, sample_rate),
+        }
+    }
+
+    /// Get the next stereo frame,
+    /// or `None` if the [`Completion`] is met.
+    ///
+    /// Mono audio units are duplicated to both channels.
+    pub(crate) fn next_frame(&mut self) -> Option<[f32; 2]> {
+        if self.end.finished {
+            return None;
+        }
+
+        if self.position == self.len {
+            self.end.check_flag();
+
+            if self.end.finished {
+                return None;
+            }
+
+            self.render_block();
+        }
+
+        let frame = [self.buffer[0][self.position], self.buffer[1][self.position]];
+        self.position += 1;
+        self.end.advance(frame);
+
+        if let Some(clock) = &mut self.clock {
+            clock.set(self.frame - (self.len - self.position) as u64);
+        }
+
+        Some(frame)
+    }
+
+    /// Write the peak amplitude of each rendered block into the given variable.
+    pub(crate) fn with_meter(mut self, meter: Option<Shared<f32>>) -> Self {
+        self.meter = meter;
+        self
+    }
+
+    /// Apply the received parameter events at their exact frame.
+    ///
+    /// Blocks are split at the frame of each event.
+    pub(crate) fn with_events(mut self, events: ParamEvents) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Report the number of served frames to the given clocks.
+    pub(crate) fn with_clock(mut self, clock: ClockWriter) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Multiply the channels by the given gains.
+    ///
+    /// If the DSP source is played in mono,
+    /// both channels are multiplied by the attenuation of the gains instead.
+    pub(crate) fn with_spatial(mut self, gains: Option<Arc<SpatialGains>>, mono: bool) -> Self {
+        self.spatial = gains.map(|gains| Spatial {
+            gains,
+            mono,
+            current: None,
+        });
+        self
+    }
+
+    /// Whether the [`Completion`] is met.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.end.finished
+    }
+
+    fn render_block(&mut self) {
+        let size = self.block_size;
+
+        self.receive_events();
+
+        let mut offset = 0;
+
+        while offset < size {
+            let frame = self.frame + offset as u64;
+
+            while let Some(event) = self.pending.front().filter(|event| event.frame <= frame) {
+                event.parameter.set_value(event.value);
+                self.pending.pop_front();
+            }
+
+            // Render until the next event, or the end of the block.
+            #[allow(clippy::cast_possible_truncation)]
+            let end = self.pending.front().map_or(size, |event| {
+                size.min(offset + (event.frame - frame) as usize)
+            });
+
+            self.render_range(offset, end);
+            offset = end;
+        }
+
+        let [left, right] = &self.buffer;
+        let (left, right) = (&left[..size], &right[..size]);
+
+        if let Some(meter) = &self.meter {
+            let peak = left
+                .iter()
+                .chain(right.iter())
+                .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+            meter.set_value(peak);
+        }
+
+        self.apply_spatial();
+
+        self.frame += size as u64;
+        self.len = size;
+        self.position = 0;
+    }
+
+    /// Render the frames in the given range of the buffer.
+    fn render_range(&mut self, start: usize, end: usize) {
+        for (input, buffer) in &mut self.inputs {
+            buffer[start..end].fill(input.value());
+        }
+
+        // A fixed array, so no memory is allocated on the audio thread.
+        let mut inputs: [&[f32]; MAX_INPUTS] = [&[]; MAX_INPUTS];
+
+        for (slice, (_, buffer)) in inputs.iter_mut().zip(&self.inputs) {
+            *slice = &buffer[start..end];
+        }
+
+        let inputs = &inputs[..self.inputs.len()];
+
+        let [left, right] = &mut self.buffer;
+        let (left, right) = (&mut left[start..end], &mut right[start..end]);
+
+        if self.audio_unit.outputs() == 1 {
+            self.audio_unit
+                .process(end - start, inputs, &mut [&mut *left]);
+            right.copy_from_slice(left);
+        } else {
+            self.audio_unit
+                .process(end - start, inputs, &mut [&mut *left, &mut *right]);
+        }
+    }
+
+    /// Ramp the channels from the previous gains to the current gains.
+    #[allow(clippy::cast_precision_loss)]
+    fn apply_spatial(&mut self) {
+        let Some(spatial) = &mut self.spatial else {
+            return;
+        };
+
+        let size = self.block_size;
+        let target = spatial.target();
+        let start = spatial.current.unwrap_or(target);
+
+        for ((buffer, start), target) in self.buffer.iter_mut().zip(start).zip(target) {
+            let step = (target - start) / size as f32;
+
+            for (index, sample) in buffer[..size].iter_mut().enumerate() {
+                *sample *= start + step * (index + 1) as f32;
+            }
+        }
+
+        spatial.current = Some(target);
+    }
+
+    /// Move the received events into the pending events, sorted by frame.
+    fn receive_events(&mut self) {
+        let Some(events) = &self.events else {
+            return;
+        };
+
+        while self.pending.len() < MAX_PENDING_EVENTS {
+            let Some(event) = events.try_recv() else {
+                break;
+            };
+
+            // Events on the same frame keep the order they were sent in.
+            let index = self
+                .pending
+                .partition_point(|pending| pending.frame <= event.frame);
+            self.pending.insert(index, event);
+        }
+    }
+}
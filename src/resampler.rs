@@ -0,0 +1,144 @@
+//! Module for [`Resampler`],
+//! which converts frames from the sample rate of a DSP graph
+//! to the sample rate requested by the backend.
+
+use crate::dsp_source::ResampleQuality;
+
+/// Resamples a stream of stereo frames.
+///
+/// The frames are pulled lazily from the source,
+/// so the source only advances as fast as its own sample rate requires.
+pub(crate) struct Resampler {
+    quality: ResampleQuality,
+    /// The last four frames of the source.
+    /// The output is interpolated between `history[1]` and `history[2]`.
+    history: [[f32; 2]; 4],
+    /// The fractional position between `history[1]` and `history[2]`.
+    position: f64,
+    primed: bool,
+}
+
+impl Resampler {
+    pub(crate) fn new(quality: ResampleQuality) -> Self {
+        Self {
+            quality,
+            history: [[0.0; 2]; 4],
+            position: 0.0,
+            primed: false,
+        }
+    }
+
+    /// Get the next resampled frame.
+    ///
+    /// `step` is the number of source frames per output frame,
+    /// which is the source sample rate divided by the output sample rate.
+    pub(crate) fn next_frame(
+        &mut self,
+        step: f64,
+        mut next_source_frame: impl FnMut() -> [f32; 2],
+    ) -> [f32; 2] {
+        if !self.primed {
+            let first = next_source_frame();
+            self.history = [first, first, next_source_frame(), next_source_frame()];
+            self.primed = true;
+        }
+
+        let frame = self.interpolate();
+
+        self.position += step;
+        while self.position >= 1.0 {
+            self.position -= 1.0;
+            self.history.rotate_left(1);
+            self.history[3] = next_source_frame();
+        }
+
+        frame
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn interpolate(&self) -> [f32; 2] {
+        let t = self.position as f32;
+        let [h0, h1, h2, h3] = self.history;
+
+        let channel = |c: usize| match self.quality {
+            ResampleQuality::Nearest => {
+                if t < 0.5 {
+                    h1[c]
+                } else {
+                    h2[c]
+                }
+            }
+            ResampleQuality::Linear => h1[c] + (h2[c] - h1[c]) * t,
+            ResampleQuality::Cubic => {
+                // Catmull-Rom spline
+                let c1 = 0.5 * (h2[c] - h0[c]);
+                let c2 = h0[c] - 2.5 * h1[c] + 2.0 * h2[c] - 0.5 * h3[c];
+                let c3 = 0.5 * (h3[c] - h0[c]) + 1.5 * (h1[c] - h2[c]);
+                ((c3 * t + c2) * t + c1) * t + h1[c]
+            }
+        };
+
+        [channel(0), channel(1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::float_cmp)]
+
+    use {super::Resampler, crate::dsp_source::ResampleQuality};
+
+    fn ramp() -> impl FnMut() -> [f32; 2] {
+        let mut value = 0.0;
+        move || {
+            let frame = [value, -value];
+            value += 1.0;
+            frame
+        }
+    }
+
+    #[test]
+    fn same_sample_rate_passes_through() {
+        for quality in [
+            ResampleQuality::Nearest,
+            ResampleQuality::Linear,
+            ResampleQuality::Cubic,
+        ] {
+            let mut resampler = Resampler::new(quality);
+            let mut source = ramp();
+
+            for expected in 0..100 {
+                #[allow(clippy::cast_precision_loss)]
+                let expected = expected as f32;
+                assert_eq!(
+                    resampler.next_frame(1.0, &mut source),
+                    [expected, -expected]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn upsampling_interpolates() {
+        let mut resampler = Resampler::new(ResampleQuality::Linear);
+        let mut source = ramp();
+
+        let frames: Vec<_> = (0..6)
+            .map(|_| resampler.next_frame(0.5, &mut source)[0])
+            .collect();
+
+        assert_eq!(frames, [0.0, 0.5, 1.0, 1.5, 2.0, 2.5]);
+    }
+
+    #[test]
+    fn downsampling_skips_frames() {
+        let mut resampler = Resampler::new(ResampleQuality::Cubic);
+        let mut source = ramp();
+
+        let frames: Vec<_> = (0..4)
+            .map(|_| resampler.next_frame(2.0, &mut source)[0])
+            .collect();
+
+        assert_eq!(frames, [0.0, 2.0, 4.0, 6.0]);
+    }
+}
@@ -0,0 +1,190 @@
+//! Module for [`StaticRenderer`],
+//! which renders static DSP sources in the background.
+
+use {
+    crate::{
+        backend::{Backend, DefaultBackend},
+        dsp_error::DspError,
+        dsp_source::DspSource,
+    },
+    bevy::{
+        prelude::{Assets, Event, EventWriter, Handle, ResMut, Resource},
+        tasks::AsyncComputeTaskPool,
+    },
+    std::{
+        marker::PhantomData,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc, Mutex, PoisonError,
+        },
+    },
+};
+
+/// Renders static DSP sources in the background,
+/// using the [`AsyncComputeTaskPool`].
+///
+/// Long static DSP sources can take a while to render.
+/// Rendering them here avoids hitching the frame
+/// that would otherwise render them.
+///
+/// Once a render is done, the audio source is added to its [`Assets`],
+/// and a [`StaticRenderFinished`] event is sent.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// # fn ambience() -> impl AudioUnit32 { pink() }
+/// fn start_render(mut renderer: ResMut<StaticRenderer>, dsp_manager: Res<DspManager>) {
+///     if let Ok(dsp_source) = dsp_manager.try_get_graph(ambience) {
+///         renderer.render(dsp_source);
+///     }
+/// }
+///
+/// fn on_render_finished(mut events: EventReader<StaticRenderFinished>) {
+///     for event in events.iter() {
+///         match &event.result {
+///             Ok(handle) => info!("Render {:?} is ready: {handle:?}", event.id),
+///             Err(err) => error!("{err}"),
+///         }
+///     }
+/// }
+/// ```
+#[derive(Resource)]
+pub struct StaticRenderer<B: Backend = DefaultBackend> {
+    next_id: u64,
+    pending: Vec<PendingRender<B>>,
+}
+
+/// The ID of a render started by the [`StaticRenderer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderId(u64);
+
+/// An event sent when a render started by the [`StaticRenderer`] is done.
+#[derive(Event)]
+pub struct StaticRenderFinished<B: Backend = DefaultBackend> {
+    /// The ID returned by [`StaticRenderer::render`].
+    pub id: RenderId,
+    /// The handle of the rendered audio source,
+    /// or the error if it cannot be rendered.
+    pub result: Result<Handle<B::StaticAudioSource>, DspError>,
+}
+
+/// The result of a render, set by its thread once it is done.
+type RenderResult<B> = Arc<Mutex<Option<Result<<B as Backend>::StaticAudioSource, DspError>>>>;
+
+struct PendingRender<B: Backend> {
+    id: RenderId,
+    progress: Arc<RenderProgress>,
+    result: RenderResult<B>,
+    _backend: PhantomData<B>,
+}
+
+/// The progress of a render, from 0 to 1.
+#[derive(Default)]
+pub(crate) struct RenderProgress(AtomicU32);
+
+impl RenderProgress {
+    pub(crate) fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set(&self, progress: f32) {
+        self.0.store(progress.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl<B: Backend> Default for StaticRenderer<B> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<B: Backend> StaticRenderer<B> {
+    /// Start rendering the given static DSP source in the background.
+    ///
+    /// If the DSP source is dynamic,
+    /// the [`StaticRenderFinished`] event contains [`DspError::DynamicSource`].
+    pub fn render(&mut self, dsp_source: DspSource) -> RenderId {
+        let id = RenderId(self.next_id);
+        self.next_id += 1;
+
+        let progress = Arc::new(RenderProgress::default());
+        let result = Arc::new(Mutex::new(None));
+
+        let task_progress = progress.clone();
+        let task_result = result.clone();
+
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                let dsp_source = DspSource {
+                    progress: Some(task_progress.clone()),
+                    ..dsp_source
+                };
+                let audio_source = B::try_convert_to_audio_source(dsp_source);
+
+                task_progress.set(1.0);
+                *task_result.lock().unwrap_or_else(PoisonError::into_inner) = Some(audio_source);
+            })
+            .detach();
+
+        self.pending.push(PendingRender {
+            id,
+            progress,
+            result,
+            _backend: PhantomData,
+        });
+
+        id
+    }
+
+    /// The progress of the given render, from 0 to 1.
+    ///
+    /// Returns `None` if the render is already finished.
+    #[must_use]
+    pub fn progress(&self, id: RenderId) -> Option<f32> {
+        self.pending
+            .iter()
+            .find(|pending| pending.id == id)
+            .map(|pending| pending.progress.get())
+    }
+
+    /// Whether the given render is still in progress.
+    #[must_use]
+    pub fn is_rendering(&self, id: RenderId) -> bool {
+        self.pending.iter().any(|pending| pending.id == id)
+    }
+}
+
+/// Add the finished renders to their [`Assets`],
+/// and send a [`StaticRenderFinished`] event for each.
+pub(crate) fn finish_static_renders<B: Backend>(
+    mut renderer: ResMut<StaticRenderer<B>>,
+    assets: Option<ResMut<Assets<B::StaticAudioSource>>>,
+    mut events: EventWriter<StaticRenderFinished<B>>,
+) {
+    // Keep the finished renders until the audio plugin is added.
+    let Some(mut assets) = assets else {
+        return;
+    };
+
+    renderer.pending.retain(|pending| {
+        let Some(result) = pending
+            .result
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        else {
+            return true;
+        };
+
+        events.send(StaticRenderFinished {
+            id: pending.id,
+            result: result.map(|audio_source| assets.add(audio_source)),
+        });
+
+        false
+    });
+}
@@ -0,0 +1,332 @@
+//! Module for [`VoiceAllocator`],
+//! which plays notes on a fixed number of voices.
+
+use {
+    crate::dsp_source::DspSource,
+    bevy::prelude::Resource,
+    fundsp::hacker32::{shared, Shared},
+};
+
+/// Plays notes of a DSP graph on a fixed number of voices.
+///
+/// Each voice is an instance of the given DSP source with its own
+/// [`Controls`](crate::controls::Controls).
+/// The inputs of the DSP graph are fed from the [`FREQUENCY`](Self::FREQUENCY),
+/// [`GATE`](Self::GATE) and [`VELOCITY`](Self::VELOCITY) parameters, in that order.
+/// DSP graphs with only two inputs ignore the velocity.
+///
+/// The voices must be played once using any backend,
+/// after which notes are played by [`VoiceAllocator::note_on`]
+/// and [`VoiceAllocator::note_off`].
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_fundsp::prelude::*;
+/// fn synth() -> impl AudioUnit32 {
+///     // The inputs are the frequency and the gate.
+///     saw() * adsr_live(0.01, 0.2, 0.5, 0.3) >> split::<U2>() * 0.2
+/// }
+///
+/// fn setup(mut commands: Commands, dsp_manager: Res<DspManager>) {
+///     let Ok(dsp_source) = dsp_manager.try_get_graph(synth) else { return; };
+///     let voice_allocator = VoiceAllocator::new(&dsp_source, 8);
+///
+///     for voice in voice_allocator.voices() {
+///         // Play the voice using the backend...
+///     }
+///
+///     commands.insert_resource(voice_allocator);
+/// }
+///
+/// fn play_keys(input: Res<Input<KeyCode>>, mut voice_allocator: ResMut<VoiceAllocator>) {
+///     if input.just_pressed(KeyCode::A) {
+///         voice_allocator.note_on(60, 1.0);
+///     }
+///     if input.just_released(KeyCode::A) {
+///         voice_allocator.note_off(60);
+///     }
+/// }
+/// ```
+#[derive(Resource)]
+pub struct VoiceAllocator {
+    voices: Vec<Voice>,
+    stealing: VoiceStealing,
+    /// Increases on every note on and note off,
+    /// so the voices can be ordered by age.
+    time: u64,
+}
+
+/// Which voice is stopped when a note is played
+/// while every voice is busy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VoiceStealing {
+    /// Stop the voice whose note started first.
+    #[default]
+    Oldest,
+    /// Stop the voice with the lowest amplitude in its last rendered block.
+    Quietest,
+}
+
+struct Voice {
+    dsp_source: DspSource,
+    /// The held note, if any.
+    note: Option<u8>,
+    /// When the note started, or when it was released.
+    since: u64,
+    level: Shared<f32>,
+}
+
+impl Voice {
+    fn level(&self) -> f32 {
+        self.level.value()
+    }
+}
+
+impl VoiceAllocator {
+    /// The parameter fed to the first input of the DSP graph,
+    /// which is the frequency of the note in Hz.
+    pub const FREQUENCY: &'static str = "frequency";
+    /// The parameter fed to the second input of the DSP graph,
+    /// which is 1 while the note is held, and 0 otherwise.
+    pub const GATE: &'static str = "gate";
+    /// The parameter fed to the third input of the DSP graph,
+    /// which is the velocity of the note from 0 to 1.
+    pub const VELOCITY: &'static str = "velocity";
+
+    /// Create a voice allocator with the given number of voices.
+    ///
+    /// The DSP source should be dynamic.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the polyphony is 0.
+    #[must_use]
+    pub fn new(dsp_source: &DspSource, polyphony: usize) -> Self {
+        assert!(polyphony > 0, "There must be at least one voice.");
+
+        let voices = (0..polyphony)
+            .map(|_| {
+                let level = shared(0.0);
+                let mut dsp_source = dsp_source.instance().with_inputs(&[
+                    Self::FREQUENCY,
+                    Self::GATE,
+                    Self::VELOCITY,
+                ]);
+                dsp_source.meter = Some(level.clone());
+
+                let controls = dsp_source.controls();
+                controls.set(Self::FREQUENCY, 0.0);
+                controls.set(Self::GATE, 0.0);
+                controls.set(Self::VELOCITY, 0.0);
+
+                Voice {
+                    dsp_source,
+                    note: None,
+                    since: 0,
+                    level,
+                }
+            })
+            .collect();
+
+        Self {
+            voices,
+            stealing: VoiceStealing::default(),
+            time: 0,
+        }
+    }
+
+    /// Set which voice is stopped when every voice is busy.
+    ///
+    /// By default, this is [`VoiceStealing::Oldest`].
+    #[must_use]
+    pub fn with_stealing(mut self, stealing: VoiceStealing) -> Self {
+        self.stealing = stealing;
+        self
+    }
+
+    /// The DSP sources of each voice.
+    ///
+    /// Each of these must be played once.
+    pub fn voices(&self) -> impl Iterator<Item = &DspSource> {
+        self.voices.iter().map(|voice| &voice.dsp_source)
+    }
+
+    /// The number of voices.
+    #[must_use]
+    pub fn polyphony(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// The notes that are currently held.
+    pub fn held_notes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.voices.iter().filter_map(|voice| voice.note)
+    }
+
+    /// Play the given MIDI note with the given velocity from 0 to 1.
+    ///
+    /// If the note is already held, its voice is played again.
+    /// Otherwise, a free voice is used.
+    /// If every voice is busy, one of them is stolen,
+    /// see [`VoiceStealing`].
+    pub fn note_on(&mut self, note: u8, velocity: f32) {
+        self.time += 1;
+
+        let index = self.find_voice(note);
+        let voice = &mut self.voices[index];
+
+        voice.note = Some(note);
+        voice.since = self.time;
+
+        let controls = voice.dsp_source.controls();
+        controls.set(Self::FREQUENCY, note_to_hz(note));
+        controls.set(Self::VELOCITY, velocity.clamp(0.0, 1.0));
+        controls.set(Self::GATE, 1.0);
+    }
+
+    /// Release the given MIDI note.
+    pub fn note_off(&mut self, note: u8) {
+        self.time += 1;
+
+        for voice in &mut self.voices {
+            if voice.note == Some(note) {
+                voice.note = None;
+                voice.since = self.time;
+                voice.dsp_source.controls().set(Self::GATE, 0.0);
+            }
+        }
+    }
+
+    /// Set the value of the given parameter of every voice.
+    ///
+    /// See [`Controls::set`](crate::controls::Controls::set).
+    pub fn set(&self, name: &str, value: f32) {
+        for voice in &self.voices {
+            voice.dsp_source.controls().set(name, value);
+        }
+    }
+
+    /// Release every held note.
+    pub fn all_notes_off(&mut self) {
+        let notes: Vec<u8> = self.held_notes().collect();
+
+        for note in notes {
+            self.note_off(note);
+        }
+    }
+
+    fn find_voice(&self, note: u8) -> usize {
+        let voices = self.voices.iter().enumerate();
+
+        if let Some((index, _)) = voices.clone().find(|(_, voice)| voice.note == Some(note)) {
+            return index;
+        }
+
+        let (free, busy): (Vec<_>, Vec<_>) = voices.partition(|(_, voice)| voice.note.is_none());
+        let candidates = if free.is_empty() { busy } else { free };
+
+        let chosen = match self.stealing {
+            VoiceStealing::Oldest => candidates.into_iter().min_by_key(|(_, voice)| voice.since),
+            VoiceStealing::Quietest => candidates
+                .into_iter()
+                .min_by(|(_, a), (_, b)| a.level().total_cmp(&b.level())),
+        };
+
+        chosen.map_or(0, |(index, _)| index)
+    }
+}
+
+/// Convert a MIDI note into its frequency in Hz.
+#[must_use]
+pub fn note_to_hz(note: u8) -> f32 {
+    440.0 * 2.0_f32.powf((f32::from(note) - 69.0) / 12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::wildcard_imports)]
+
+    use {
+        super::{note_to_hz, VoiceAllocator, VoiceStealing},
+        crate::dsp_source::{DspSource, SourceType},
+        fundsp::hacker32::*,
+    };
+
+    fn voice_allocator(polyphony: usize) -> VoiceAllocator {
+        // The frequency times the gate.
+        let dsp_source = DspSource::new(|| pass() * pass(), 44100.0, SourceType::Dynamic);
+
+        VoiceAllocator::new(&dsp_source, polyphony)
+    }
+
+    fn render(voice_allocator: &VoiceAllocator) -> Vec<f32> {
+        voice_allocator
+            .voices()
+            .map(|voice| voice.clone().with_block_size(1).into_iter().next().unwrap()[0])
+            .collect()
+    }
+
+    #[test]
+    fn notes_are_fed_to_the_graph() {
+        let mut voice_allocator = voice_allocator(2);
+
+        assert_eq!(render(&voice_allocator), [0.0, 0.0]);
+
+        voice_allocator.note_on(69, 1.0);
+        voice_allocator.note_on(57, 1.0);
+
+        assert_eq!(render(&voice_allocator), [440.0, 220.0]);
+
+        voice_allocator.note_off(69);
+
+        assert_eq!(render(&voice_allocator), [0.0, 220.0]);
+        assert_eq!(voice_allocator.held_notes().collect::<Vec<_>>(), [57]);
+    }
+
+    #[test]
+    fn oldest_voice_is_stolen() {
+        let mut voice_allocator = voice_allocator(2);
+
+        voice_allocator.note_on(60, 1.0);
+        voice_allocator.note_on(62, 1.0);
+        voice_allocator.note_on(64, 1.0);
+
+        let mut held: Vec<_> = voice_allocator.held_notes().collect();
+        held.sort_unstable();
+
+        assert_eq!(held, [62, 64]);
+
+        // A released voice is used before stealing.
+        voice_allocator.note_off(62);
+        voice_allocator.note_on(65, 1.0);
+
+        let mut held: Vec<_> = voice_allocator.held_notes().collect();
+        held.sort_unstable();
+
+        assert_eq!(held, [64, 65]);
+    }
+
+    #[test]
+    fn quietest_voice_is_stolen() {
+        let mut voice_allocator = voice_allocator(2).with_stealing(VoiceStealing::Quietest);
+
+        voice_allocator.note_on(60, 1.0);
+        voice_allocator.note_on(48, 1.0);
+
+        // Update the level of each voice.
+        render(&voice_allocator);
+
+        voice_allocator.note_on(72, 1.0);
+
+        let mut held: Vec<_> = voice_allocator.held_notes().collect();
+        held.sort_unstable();
+
+        assert_eq!(held, [60, 72]);
+    }
+
+    #[test]
+    fn note_frequencies() {
+        assert!((note_to_hz(69) - 440.0).abs() < f32::EPSILON);
+        assert!((note_to_hz(81) - 880.0).abs() < 1e-3);
+        assert!((note_to_hz(60) - 261.626).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,216 @@
+//! Module for writing rendered frames as WAV files.
+
+use {
+    crate::dsp_source::{Channels, SampleFormat},
+    std::io::{self, Write},
+};
+
+/// Write the given stereo frames as a WAV file.
+///
+/// If `channels` is [`Channels::Mono`],
+/// both channels are averaged into one.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn write_wav(
+    writer: &mut dyn Write,
+    frames: &[[f32; 2]],
+    sample_rate: u32,
+    channels: Channels,
+    sample_format: SampleFormat,
+) -> io::Result<()> {
+    let channel_count: u16 = match channels {
+        Channels::Mono => 1,
+        Channels::Stereo => 2,
+    };
+    let (format_tag, bits_per_sample): (u16, u16) = match sample_format {
+        SampleFormat::Int16 => (1, 16),
+        SampleFormat::Int24 => (1, 24),
+        SampleFormat::Int32 => (1, 32),
+        SampleFormat::Float32 => (3, 32),
+    };
+    let block_align = channel_count * bits_per_sample / 8;
+    let data_len = u32::try_from(frames.len() * usize::from(block_align))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "WAV file is too long."))?;
+
+    // Non-PCM formats need the extended format chunk and a fact chunk.
+    let is_float = sample_format == SampleFormat::Float32;
+    let (fmt_len, fact_len) = if is_float { (18, 12) } else { (16, 0) };
+    let frame_count = data_len / u32::from(block_align);
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(20 + fmt_len + fact_len + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&fmt_len.to_le_bytes())?;
+    writer.write_all(&format_tag.to_le_bytes())?;
+    writer.write_all(&channel_count.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&bits_per_sample.to_le_bytes())?;
+
+    if is_float {
+        writer.write_all(&0_u16.to_le_bytes())?;
+
+        writer.write_all(b"fact")?;
+        writer.write_all(&4_u32.to_le_bytes())?;
+        writer.write_all(&frame_count.to_le_bytes())?;
+    }
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+
+    let mut write_sample = |sample: f32| -> io::Result<()> {
+        let Some(scale) = scale(sample_format) else {
+            return writer.write_all(&sample.to_le_bytes());
+        };
+
+        let sample = quantize(sample, scale);
+        match sample_format {
+            SampleFormat::Int16 => writer.write_all(&(sample as i16).to_le_bytes()),
+            SampleFormat::Int24 => writer.write_all(&sample.to_le_bytes()[..3]),
+            SampleFormat::Int32 | SampleFormat::Float32 => writer.write_all(&sample.to_le_bytes()),
+        }
+    };
+
+    for &[left, right] in frames {
+        match channels {
+            Channels::Mono => write_sample(f32::midpoint(left, right))?,
+            Channels::Stereo => {
+                write_sample(left)?;
+                write_sample(right)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Scale the sample to the range of an integer format and round it.
+///
+/// Decoders divide integer samples by the same scale,
+/// so samples that fit in the format are decoded exactly.
+#[allow(clippy::cast_possible_truncation)]
+fn quantize(sample: f32, scale: f64) -> i32 {
+    (f64::from(sample) * scale)
+        .round()
+        .clamp(-scale, scale - 1.0) as i32
+}
+
+/// The scale of each integer format, which is 2 to the power of its bits minus one,
+/// or `None` if the format is float.
+fn scale(sample_format: SampleFormat) -> Option<f64> {
+    match sample_format {
+        SampleFormat::Int16 => Some(32_768.0),
+        SampleFormat::Int24 => Some(8_388_608.0),
+        SampleFormat::Int32 => Some(2_147_483_648.0),
+        SampleFormat::Float32 => None,
+    }
+}
+
+/// Reduce the precision of the frames to match the given format,
+/// and downmix them if the channels are [`Channels::Mono`].
+///
+/// This is used by backends that play frames directly
+/// instead of decoding a WAV file,
+/// so they sound the same as the other backends.
+pub(crate) fn apply_format(
+    frames: &mut [[f32; 2]],
+    channels: Channels,
+    sample_format: SampleFormat,
+) {
+    for frame in frames {
+        if channels == Channels::Mono {
+            let mono = f32::midpoint(frame[0], frame[1]);
+            *frame = [mono, mono];
+        }
+
+        if let Some(scale) = scale(sample_format) {
+            #[allow(clippy::cast_possible_truncation)]
+            let dequantize = |sample| (f64::from(quantize(sample, scale)) / scale) as f32;
+            *frame = frame.map(dequantize);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::float_cmp, clippy::cast_possible_truncation)]
+
+    use {
+        super::{apply_format, write_wav},
+        crate::dsp_source::{Channels, SampleFormat},
+    };
+
+    #[test]
+    fn header_and_length() {
+        let frames = [[0.5, -0.5]; 10];
+
+        for (channels, sample_format, header_len, bytes_per_frame) in [
+            (Channels::Stereo, SampleFormat::Int16, 44, 4),
+            (Channels::Mono, SampleFormat::Int16, 44, 2),
+            (Channels::Stereo, SampleFormat::Int24, 44, 6),
+            (Channels::Mono, SampleFormat::Int32, 44, 4),
+            (Channels::Stereo, SampleFormat::Float32, 58, 8),
+        ] {
+            let mut buffer = Vec::new();
+            write_wav(&mut buffer, &frames, 44100, channels, sample_format).unwrap();
+
+            let riff_len = u32::from_le_bytes(buffer[4..8].try_into().unwrap());
+
+            assert_eq!(&buffer[..4], b"RIFF");
+            assert_eq!(&buffer[8..12], b"WAVE");
+            assert_eq!(buffer.len(), header_len + 10 * bytes_per_frame);
+            assert_eq!(riff_len as usize, buffer.len() - 8);
+        }
+    }
+
+    #[test]
+    fn float_samples_are_exact() {
+        let mut buffer = Vec::new();
+        write_wav(
+            &mut buffer,
+            &[[0.123, -0.456]],
+            44100,
+            Channels::Stereo,
+            SampleFormat::Float32,
+        )
+        .unwrap();
+
+        assert_eq!(buffer[20..22], 3_u16.to_le_bytes());
+        assert_eq!(&buffer[38..42], b"fact");
+        assert_eq!(buffer[46..50], 1_u32.to_le_bytes());
+        assert_eq!(buffer[58..62], 0.123_f32.to_le_bytes());
+        assert_eq!(buffer[62..66], (-0.456_f32).to_le_bytes());
+    }
+
+    #[test]
+    fn integer_samples_are_scaled_like_decoders() {
+        let mut buffer = Vec::new();
+        write_wav(
+            &mut buffer,
+            &[[0.25, 1.0], [0.1, -1.0]],
+            44100,
+            Channels::Stereo,
+            SampleFormat::Int32,
+        )
+        .unwrap();
+
+        let sample =
+            |i: usize| i32::from_le_bytes(buffer[44 + i * 4..48 + i * 4].try_into().unwrap());
+
+        assert_eq!(sample(0), 1 << 29);
+        assert_eq!(sample(1), i32::MAX);
+        assert_eq!(
+            sample(2),
+            (f64::from(0.1_f32) * 2_147_483_648.0).round() as i32
+        );
+        assert_eq!(sample(3), i32::MIN);
+
+        let mut frames = [[0.25, -0.75], [0.1, 1.0]];
+        apply_format(&mut frames, Channels::Stereo, SampleFormat::Int16);
+
+        assert_eq!(frames[0], [0.25, -0.75]);
+        assert_eq!(frames[1], [3277.0 / 32768.0, 32767.0 / 32768.0]);
+    }
+}